@@ -0,0 +1,305 @@
+use maze::Maze;
+use search::{Graph, QueueEntry};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io::BufRead;
+use stored::{LoadError, Stored};
+
+/// Most constraint tree nodes CBS expands before giving up.
+const CBS_LIMIT: usize = 10000;
+
+/// Several agents sharing one maze. Each lowercase letter is an agent's start
+/// and the matching uppercase letter its goal; `x` is never an agent, since
+/// `X` already marks the maze's own goal. Every tick each agent moves to a
+/// neighbouring cell or waits, and no two agents may share a cell or swap
+/// places. Agents stay on their goals once they're done.
+pub struct AgentMaze {
+    maze: Maze,
+    labels: Vec<char>,
+    starts: Vec<(usize, usize)>,
+    goals: Vec<(usize, usize)>,
+    // steps from every cell to each agent's goal
+    to_goal: Vec<HashMap<(usize, usize), usize>>,
+}
+
+/// Where each agent is at each tick. Agents stay put after their path ends.
+pub struct Plan {
+    pub paths: Vec<Vec<(usize, usize)>>,
+    /// Sum over agents of the tick they reach their goal for good.
+    pub cost: usize,
+    /// Single agent searches run to find the plan.
+    pub searches: usize,
+}
+
+enum Conflict {
+    Vertex(usize, usize, (usize, usize), usize),
+    // the first agent moves between the cells one way, the second the other
+    Swap(usize, usize, (usize, usize), (usize, usize), usize),
+}
+
+/// What one agent must avoid. Moves are keyed by the tick they arrive.
+#[derive(Clone, Default)]
+struct Constraints {
+    cells: HashSet<((usize, usize), usize)>,
+    moves: HashSet<((usize, usize), (usize, usize), usize)>,
+    // cells taken for good from some tick on, by agents that have finished
+    parked: HashMap<(usize, usize), usize>,
+}
+
+impl Constraints {
+    fn allows(&self, from: (usize, usize), to: (usize, usize), t: usize) -> bool {
+        !self.cells.contains(&(to, t)) && !self.moves.contains(&(from, to, t)) &&
+        self.parked.get(&to).map_or(true, |&since| t < since)
+    }
+
+    fn with_cell(&self, cell: (usize, usize), t: usize) -> Constraints {
+        let mut rules = self.clone();
+        rules.cells.insert((cell, t));
+        rules
+    }
+
+    fn with_move(&self, from: (usize, usize), to: (usize, usize), t: usize) -> Constraints {
+        let mut rules = self.clone();
+        rules.moves.insert((from, to, t));
+        rules
+    }
+
+    /// The first tick from which `cell` is never blocked again.
+    fn free_from(&self, cell: (usize, usize)) -> usize {
+        self.cells.iter().filter(|&&(c, _)| c == cell).map(|&(_, t)| t + 1).max().unwrap_or(0)
+    }
+}
+
+fn at(path: &Vec<(usize, usize)>, t: usize) -> (usize, usize) {
+    path[if t < path.len() { t } else { path.len() - 1 }]
+}
+
+fn cost(paths: &Vec<Vec<(usize, usize)>>) -> usize {
+    paths.iter().map(|p| p.len() - 1).sum()
+}
+
+impl Stored for AgentMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let mut starts = HashMap::new();
+        let mut goals = HashMap::new();
+        let mut dupe = None;
+        let maze = try!(Maze::load_map_with(reader, |ch, x, y| {
+            let before = match ch {
+                'a'...'z' if ch != 'x' => starts.insert(ch, (x, y)),
+                'A'...'Z' => goals.insert(ch.to_ascii_lowercase(), (x, y)),
+                _ => return None,
+            };
+            if before.is_some() && dupe.is_none() {
+                dupe = Some(ch);
+            }
+            Some(true)
+        }));
+        if let Some(ch) = dupe {
+            return Err(LoadError::Invalid(format!("more than one '{}'", ch)));
+        }
+        let mut labels: Vec<char> = starts.keys().cloned().collect();
+        labels.sort();
+        if labels.is_empty() {
+            return Err(LoadError::Invalid("no agents".to_string()));
+        }
+        if labels.len() != goals.len() {
+            return Err(LoadError::Invalid("every agent needs exactly one start and one goal"
+                .to_string()));
+        }
+        let mut ends = vec![];
+        for l in &labels {
+            match goals.get(l) {
+                Some(&goal) => ends.push(goal),
+                None => return Err(LoadError::Invalid(format!("agent '{}' has no goal", l))),
+            }
+        }
+        let goals = ends;
+        let to_goal = goals.iter().map(|&g| steps_from(&maze, g)).collect();
+        Ok(AgentMaze {
+            starts: labels.iter().map(|l| starts[l]).collect(),
+            maze: maze,
+            labels: labels,
+            goals: goals,
+            to_goal: to_goal,
+        })
+    }
+}
+
+fn steps_from(maze: &Maze, from: (usize, usize)) -> HashMap<(usize, usize), usize> {
+    let mut steps = HashMap::new();
+    steps.insert(from, 0);
+    let mut frontier = VecDeque::new();
+    frontier.push_back(from);
+    while let Some(pos) = frontier.pop_front() {
+        let next_steps = steps[&pos] + 1;
+        for (_, next) in maze.neighbors(&pos) {
+            if !steps.contains_key(&next) {
+                steps.insert(next, next_steps);
+                frontier.push_back(next);
+            }
+        }
+    }
+    steps
+}
+
+impl AgentMaze {
+    pub fn agents(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Space-time A* for one agent, in ticks.
+    fn plan_one(&self, agent: usize, rules: &Constraints) -> Option<Vec<(usize, usize)>> {
+        let goal = self.goals[agent];
+        let to_goal = &self.to_goal[agent];
+        let start = self.starts[agent];
+        let h = |pos: &(usize, usize)| to_goal.get(pos).cloned();
+        let done_from = rules.free_from(goal);
+        // long enough to wait out every constraint and then walk anywhere
+        let horizon = done_from + rules.parked.values().max().cloned().unwrap_or(0) +
+                      to_goal.len() + self.agents();
+        let mut prior = HashMap::new();
+        let mut frontier = BinaryHeap::new();
+        match h(&start) {
+            Some(d) => frontier.push(QueueEntry((d, d), (start, 0))),
+            None => return None,
+        }
+        prior.insert((start, 0), None);
+        while let Some(QueueEntry(_, (pos, t))) = frontier.pop() {
+            if pos == goal && t >= done_from {
+                let mut path = vec![pos];
+                let mut node = (pos, t);
+                while let Some(&Some(back)) = prior.get(&node) {
+                    path.push(back);
+                    node = (back, node.1 - 1);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if t >= horizon {
+                continue;
+            }
+            let moves = self.maze.neighbors(&pos).into_iter().map(|(_, n)| n).chain(Some(pos));
+            for next in moves {
+                if !rules.allows(pos, next, t + 1) || prior.contains_key(&(next, t + 1)) {
+                    continue;
+                }
+                if let Some(d) = h(&next) {
+                    prior.insert((next, t + 1), Some(pos));
+                    // ties go to whoever is closer to the goal
+                    frontier.push(QueueEntry((t + 1 + d, d), (next, t + 1)));
+                }
+            }
+        }
+        None
+    }
+
+    fn first_conflict(&self, paths: &Vec<Vec<(usize, usize)>>) -> Option<Conflict> {
+        let end = paths.iter().map(|p| p.len()).max().unwrap_or(0);
+        for t in 0..end {
+            for a in 0..paths.len() {
+                for b in a + 1..paths.len() {
+                    let (a_now, b_now) = (at(&paths[a], t), at(&paths[b], t));
+                    if a_now == b_now {
+                        return Some(Conflict::Vertex(a, b, a_now, t));
+                    }
+                    if t > 0 && at(&paths[a], t - 1) == b_now && at(&paths[b], t - 1) == a_now {
+                        return Some(Conflict::Swap(a, b, b_now, a_now, t));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Plans agents one at a time in label order, each avoiding the paths of
+    /// those before it. Fast, but can fail or be far from optimal when an
+    /// early agent gets in a later one's way.
+    pub fn prioritized(&self) -> Option<Plan> {
+        let mut rules = Constraints::default();
+        let mut paths = vec![];
+        for agent in 0..self.agents() {
+            let path = match self.plan_one(agent, &rules) {
+                Some(path) => path,
+                None => return None,
+            };
+            for (t, &pos) in path.iter().enumerate() {
+                rules.cells.insert((pos, t));
+                if t > 0 {
+                    rules.moves.insert((pos, path[t - 1], t));
+                }
+            }
+            rules.parked.insert(*path.last().unwrap(), path.len() - 1);
+            paths.push(path);
+        }
+        Some(Plan {
+            cost: cost(&paths),
+            paths: paths,
+            searches: self.agents(),
+        })
+    }
+
+    /// Conflict-Based Search: plans each agent alone, then for the cheapest
+    /// set of paths with a conflict, tries both ways of forbidding one of the
+    /// two agents from it. Optimal for the sum of arrival ticks.
+    pub fn cbs(&self) -> Option<Plan> {
+        let rules = vec![Constraints::default(); self.agents()];
+        let mut paths = vec![];
+        for agent in 0..self.agents() {
+            match self.plan_one(agent, &rules[agent]) {
+                Some(path) => paths.push(path),
+                None => return None,
+            }
+        }
+        let mut searches = self.agents();
+        let mut open = vec![(cost(&paths), rules, paths)];
+        let mut expanded = 0;
+        while !open.is_empty() && expanded < CBS_LIMIT {
+            let best = (0..open.len()).min_by_key(|&i| open[i].0).unwrap();
+            let (_, rules, paths) = open.swap_remove(best);
+            expanded += 1;
+            let branches: Vec<(usize, Constraints)> = match self.first_conflict(&paths) {
+                None => {
+                    return Some(Plan {
+                        cost: cost(&paths),
+                        paths: paths,
+                        searches: searches,
+                    })
+                }
+                Some(Conflict::Vertex(a, b, cell, t)) => {
+                    vec![(a, rules[a].with_cell(cell, t)), (b, rules[b].with_cell(cell, t))]
+                }
+                Some(Conflict::Swap(a, b, from, to, t)) => {
+                    vec![(a, rules[a].with_move(from, to, t)), (b, rules[b].with_move(to, from, t))]
+                }
+            };
+            for (agent, rule) in branches {
+                let mut rules = rules.clone();
+                rules[agent] = rule;
+                searches += 1;
+                if let Some(path) = self.plan_one(agent, &rules[agent]) {
+                    let mut paths = paths.clone();
+                    paths[agent] = path;
+                    open.push((cost(&paths), rules, paths));
+                }
+            }
+        }
+        None
+    }
+
+    /// Prints the maze at tick `t` of `plan`, with agents as their letters and
+    /// unreached goals as the uppercase letters.
+    pub fn print(&self, plan: &Plan, t: usize) {
+        let rows = self.maze.render_with(|x, y, ch| {
+            if let Some(i) = plan.paths.iter().position(|p| at(p, t) == (x, y)) {
+                self.labels[i]
+            } else if let Some(i) = self.goals.iter().position(|&g| g == (x, y)) {
+                self.labels[i].to_ascii_uppercase()
+            } else {
+                ch
+            }
+        });
+        for row in rows {
+            println!("{}", row);
+        }
+        println!("");
+    }
+}
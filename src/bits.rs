@@ -0,0 +1,124 @@
+use std::cmp::min;
+
+const WORD: usize = 64;
+
+/// A fixed-size set of small integers, one bit each.
+#[derive(Clone)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> BitSet {
+        BitSet { words: vec![0; (len + WORD - 1) / WORD] }
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words[i / WORD] & (1 << (i % WORD)) != 0
+    }
+
+    /// Adds `i`, returning whether it was new.
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (word, bit) = (i / WORD, 1 << (i % WORD));
+        let new = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        new
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.words[i / WORD] &= !(1 << (i % WORD));
+    }
+}
+
+/// A grid of bits, eight cells to a byte. Rows start on word boundaries so
+/// that a cell and its neighbours along a row come from at most two words.
+/// Bits past the end of a row are never set.
+#[derive(Clone)]
+pub struct BitGrid {
+    height: usize,
+    // words per row
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    /// A grid with every bit clear.
+    pub fn new(width: usize, height: usize) -> BitGrid {
+        let stride = (width + WORD - 1) / WORD;
+        BitGrid {
+            height: height,
+            stride: stride,
+            words: vec![0; stride * height],
+        }
+    }
+
+    fn word(&self, x: usize, y: usize) -> usize {
+        y * self.stride + x / WORD
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.words[self.word(x, y)] & (1 << (x % WORD)) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize) {
+        let word = self.word(x, y);
+        self.words[word] |= 1 << (x % WORD);
+    }
+
+    pub fn clear(&mut self, x: usize, y: usize) {
+        let word = self.word(x, y);
+        self.words[word] &= !(1 << (x % WORD));
+    }
+
+    /// Sets every bit from `from` up to but not including `to` on row `y`, a
+    /// word at a time.
+    pub fn set_run(&mut self, y: usize, from: usize, to: usize) {
+        let mut x = from;
+        while x < to {
+            let end = min(to, (x / WORD + 1) * WORD);
+            let bits = if end - x == WORD {
+                !0
+            } else {
+                ((1 << (end - x)) - 1) << (x % WORD)
+            };
+            let word = self.word(x, y);
+            self.words[word] |= bits;
+            x = end;
+        }
+    }
+
+    /// The bits at x - 1, x and x + 1 on row `y`, lowest first, read with a
+    /// shift or two rather than bit by bit. Bits off the ends of the row read
+    /// as clear.
+    fn triple(&self, x: usize, y: usize) -> u16 {
+        let row = &self.words[y * self.stride..(y + 1) * self.stride];
+        let (w, b) = (x / WORD, x % WORD);
+        let mut bits = if b == 0 { row[w] << 1 } else { row[w] >> (b - 1) };
+        if b == 0 && w > 0 {
+            bits |= row[w - 1] >> (WORD - 1);
+        }
+        if b == WORD - 1 && w + 1 < self.stride {
+            bits |= row[w + 1] << 2;
+        }
+        (bits & 7) as u16
+    }
+
+    /// The 3x3 block of bits centred on (x, y), row by row from the top left,
+    /// so that (x + dx, y + dy) is bit `(dy + 1) * 3 + dx + 1`. Bits off the
+    /// edges of the grid read as clear.
+    pub fn around(&self, x: usize, y: usize) -> u16 {
+        let mut bits = self.triple(x, y) << 3;
+        if y > 0 {
+            bits |= self.triple(x, y - 1);
+        }
+        if y + 1 < self.height {
+            bits |= self.triple(x, y + 1) << 6;
+        }
+        bits
+    }
+
+    /// How many bits are set.
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+}
@@ -0,0 +1,90 @@
+use generator;
+use maze::Maze;
+use rand::Rng;
+use search;
+use stats::{self, Stats};
+
+/// Fresh mazes tried before giving up.
+const ATTEMPTS: usize = 20;
+/// Mutations tried on each fresh maze before starting over.
+const MUTATIONS: usize = 200;
+
+/// Lower bounds a generated maze has to meet. Zero means no bound.
+#[derive(Clone, Copy, Default)]
+pub struct Target {
+    pub min_length: usize,
+    pub min_decisions: usize,
+    /// States depth-first search expands before finding the goal.
+    pub min_expansions: usize,
+}
+
+/// A maze that met its target, with what it scored.
+pub struct Scored {
+    pub maze: Maze,
+    pub stats: Stats,
+    pub expansions: usize,
+}
+
+fn shortfall(needed: usize, got: usize) -> f64 {
+    if got >= needed {
+        0.0
+    } else {
+        (needed - got) as f64 / needed as f64
+    }
+}
+
+impl Target {
+    pub fn is_set(&self) -> bool {
+        self.min_length > 0 || self.min_decisions > 0 || self.min_expansions > 0
+    }
+
+    fn score(&self, maze: Maze) -> (f64, Scored) {
+        let stats = stats::analyze(&maze);
+        let expansions = search::dfs_counted(&maze).1;
+        let missing = match stats.solution_length {
+            None => 3.0,
+            Some(length) => {
+                shortfall(self.min_length, length) +
+                shortfall(self.min_decisions, stats.decision_points) +
+                shortfall(self.min_expansions, expansions)
+            }
+        };
+        (missing,
+         Scored {
+             maze: maze,
+             stats: stats,
+             expansions: expansions,
+         })
+    }
+}
+
+/// Draws mazes from `fresh` and hill-climbs each with `generator::mutate`,
+/// keeping any change that doesn't fall further short of `target`, until one
+/// meets it.
+///
+/// Mutation keeps perfect mazes perfect, so `fresh` shouldn't add loops:
+/// depth-first search gets slow on mazes with many of them.
+pub fn generate<R, F>(target: &Target, mut fresh: F, rng: &mut R) -> Option<Scored>
+    where R: Rng,
+          F: FnMut(&mut R) -> Maze
+{
+    for _ in 0..ATTEMPTS {
+        let (mut missing, mut best) = target.score(fresh(rng));
+        for _ in 0..MUTATIONS {
+            if missing == 0.0 {
+                return Some(best);
+            }
+            let mut candidate = best.maze.clone();
+            generator::mutate(&mut candidate, rng);
+            let (candidate_missing, scored) = target.score(candidate);
+            if candidate_missing <= missing {
+                missing = candidate_missing;
+                best = scored;
+            }
+        }
+        if missing == 0.0 {
+            return Some(best);
+        }
+    }
+    None
+}
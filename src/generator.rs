@@ -0,0 +1,470 @@
+use bits::BitSet;
+use maze::Maze;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Builds perfect mazes on the grid used by `Maze::random`: cells sit at odd
+/// coordinates and the even coordinates between them are walls.
+pub trait MazeGenerator {
+    /// Carves passages into `maze`, which should start out as solid wall.
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R);
+
+    /// Generates a maze of odd width and height, or a toroidal maze of even
+    /// width and height, since there the walls on the first row and column
+    /// are shared with the last.
+    fn generate<R: Rng>(&self, width: usize, height: usize, wrap: bool, rng: &mut R) -> Maze {
+        let mut maze = Maze::new(width, height);
+        maze.set_wrap(wrap);
+        self.carve(&mut maze, rng);
+        if wrap {
+            // the opposite corner is right beside the start on a torus
+            maze.set_goal(width / 4 * 2 + 1, height / 4 * 2 + 1);
+        }
+        maze
+    }
+}
+
+/// Randomized depth-first backtracker. Long corridors, few branches.
+pub struct Backtracker;
+/// Randomized Prim's. Many short dead ends radiating from the start.
+pub struct Prim;
+/// Randomized Kruskal's. Uniformly scattered short dead ends.
+pub struct Kruskal;
+/// Wilson's loop-erased random walks. Uniform spanning tree.
+pub struct Wilson;
+/// Eller's row-at-a-time sets. Horizontal bias. Never wraps, even on a torus.
+pub struct Eller;
+/// Aldous-Broder random walk. Uniform spanning tree, but slow.
+pub struct AldousBroder;
+/// Recursive division. Long straight walls with a single gap each. Never
+/// wraps, even on a torus.
+pub struct Division;
+
+type Cell = (usize, usize);
+
+/// The cells of a maze, without the walls between them.
+struct Cells {
+    width: usize,
+    height: usize,
+    wrap: bool,
+}
+
+impl Cells {
+    fn of(maze: &Maze) -> Cells {
+        let (w, h) = (maze.width(), maze.height());
+        if maze.wraps() {
+            assert!(w >= 6 && h >= 6 && w % 2 == 0 && h % 2 == 0,
+                    format!("mis-sized toroidal maze {}x{}", w, h));
+            Cells {
+                width: w / 2,
+                height: h / 2,
+                wrap: true,
+            }
+        } else {
+            assert!(w > 0 && h > 0 && w % 2 != 0 && h % 2 != 0,
+                    format!("mis-sized maze {}x{}", w, h));
+            Cells {
+                width: (w - 1) / 2,
+                height: (h - 1) / 2,
+                wrap: false,
+            }
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn index(&self, (i, j): Cell) -> usize {
+        j * self.width + i
+    }
+
+    fn neighbors(&self, (i, j): Cell) -> Vec<Cell> {
+        let (cw, ch) = (self.width, self.height);
+        let mut list = Vec::with_capacity(4);
+        if i > 0 {
+            list.push((i - 1, j));
+        } else if self.wrap {
+            list.push((cw - 1, j));
+        }
+        if j > 0 {
+            list.push((i, j - 1));
+        } else if self.wrap {
+            list.push((i, ch - 1));
+        }
+        if i < cw - 1 {
+            list.push((i + 1, j));
+        } else if self.wrap {
+            list.push((0, j));
+        }
+        if j < ch - 1 {
+            list.push((i, j + 1));
+        } else if self.wrap {
+            list.push((i, 0));
+        }
+        list
+    }
+
+    fn random<R: Rng>(&self, rng: &mut R) -> Cell {
+        (rng.gen_range(0, self.width), rng.gen_range(0, self.height))
+    }
+}
+
+/// Maze coordinate of the wall between two neighbouring cells along one axis.
+/// Cells more than one apart are neighbours across the edge of a torus, and
+/// share the wall at 0.
+fn wall_between(a: usize, b: usize) -> usize {
+    if a == b {
+        2 * a + 1
+    } else if a + 1 == b || b + 1 == a {
+        a + b + 1
+    } else {
+        0
+    }
+}
+
+fn open_cell(maze: &mut Maze, (i, j): Cell) {
+    maze.set_open(2 * i + 1, 2 * j + 1);
+}
+
+fn open_between(maze: &mut Maze, (i1, j1): Cell, (i2, j2): Cell) {
+    open_cell(maze, (i1, j1));
+    open_cell(maze, (i2, j2));
+    maze.set_open(wall_between(i1, i2), wall_between(j1, j2));
+}
+
+fn close_between(maze: &mut Maze, (i1, j1): Cell, (i2, j2): Cell) {
+    maze.set_closed(wall_between(i1, i2), wall_between(j1, j2));
+}
+
+fn is_passage(maze: &Maze, (i1, j1): Cell, (i2, j2): Cell) -> bool {
+    maze.is_open(wall_between(i1, i2), wall_between(j1, j2))
+}
+
+impl MazeGenerator for Backtracker {
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R) {
+        if !maze.wraps() {
+            // the original generator, so a seed still gives the same maze
+            *maze = Maze::random(maze.width(), maze.height(), rng);
+            return;
+        }
+        let cells = Cells::of(maze);
+        let mut visited = BitSet::new(cells.count());
+        visited.insert(0);
+        open_cell(maze, (0, 0));
+        let mut stack = vec![(0, 0)];
+        while let Some(&current) = stack.last() {
+            let nexts: Vec<Cell> = cells.neighbors(current)
+                .into_iter()
+                .filter(|&n| !visited.contains(cells.index(n)))
+                .collect();
+            if let Some(&next) = rng.choose(&nexts) {
+                visited.insert(cells.index(next));
+                open_between(maze, current, next);
+                stack.push(next);
+            } else {
+                stack.pop();
+            }
+        }
+    }
+}
+
+impl MazeGenerator for Prim {
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R) {
+        let cells = Cells::of(maze);
+        let mut in_maze = BitSet::new(cells.count());
+        let first = cells.random(rng);
+        in_maze.insert(cells.index(first));
+        open_cell(maze, first);
+        let mut frontier: Vec<(Cell, Cell)> =
+            cells.neighbors(first).into_iter().map(|n| (first, n)).collect();
+        while !frontier.is_empty() {
+            let index = rng.gen_range(0, frontier.len());
+            let (from, to) = frontier.swap_remove(index);
+            if in_maze.contains(cells.index(to)) {
+                continue;
+            }
+            in_maze.insert(cells.index(to));
+            open_between(maze, from, to);
+            for next in cells.neighbors(to) {
+                if !in_maze.contains(cells.index(next)) {
+                    frontier.push((to, next));
+                }
+            }
+        }
+    }
+}
+
+impl MazeGenerator for Kruskal {
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R) {
+        let cells = Cells::of(maze);
+        let mut edges = Vec::new();
+        for j in 0..cells.height {
+            for i in 0..cells.width {
+                open_cell(maze, (i, j));
+                for n in cells.neighbors((i, j)) {
+                    if cells.index(n) > cells.index((i, j)) {
+                        edges.push(((i, j), n));
+                    }
+                }
+            }
+        }
+        rng.shuffle(&mut edges);
+        let mut parent: Vec<usize> = (0..cells.count()).collect();
+        fn find(parent: &mut Vec<usize>, mut k: usize) -> usize {
+            while parent[k] != k {
+                parent[k] = parent[parent[k]];
+                k = parent[k];
+            }
+            k
+        }
+        for (a, b) in edges {
+            let ra = find(&mut parent, cells.index(a));
+            let rb = find(&mut parent, cells.index(b));
+            if ra != rb {
+                parent[ra] = rb;
+                open_between(maze, a, b);
+            }
+        }
+    }
+}
+
+impl MazeGenerator for Wilson {
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R) {
+        let cells = Cells::of(maze);
+        let mut in_tree = BitSet::new(cells.count());
+        let root = cells.random(rng);
+        in_tree.insert(cells.index(root));
+        open_cell(maze, root);
+        for j in 0..cells.height {
+            for i in 0..cells.width {
+                if in_tree.contains(cells.index((i, j))) {
+                    continue;
+                }
+                // Random walk until we hit the tree, remembering only the
+                // last exit from each cell so that loops are erased.
+                let mut exits = HashMap::new();
+                let mut current = (i, j);
+                while !in_tree.contains(cells.index(current)) {
+                    let next = *rng.choose(&cells.neighbors(current)).unwrap();
+                    exits.insert(current, next);
+                    current = next;
+                }
+                current = (i, j);
+                while !in_tree.contains(cells.index(current)) {
+                    let next = exits[&current];
+                    in_tree.insert(cells.index(current));
+                    open_between(maze, current, next);
+                    current = next;
+                }
+            }
+        }
+    }
+}
+
+impl MazeGenerator for Eller {
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R) {
+        let cells = Cells::of(maze);
+        let (cw, ch) = (cells.width, cells.height);
+        let mut sets: Vec<Option<usize>> = vec![None; cw];
+        let mut next_set = 0;
+        for j in 0..ch {
+            for i in 0..cw {
+                open_cell(maze, (i, j));
+                if sets[i].is_none() {
+                    sets[i] = Some(next_set);
+                    next_set += 1;
+                }
+            }
+            let last_row = j + 1 == ch;
+            // Join horizontally adjacent cells from distinct sets.
+            for i in 0..cw - 1 {
+                if sets[i] != sets[i + 1] && (last_row || rng.gen()) {
+                    let (keep, replace) = (sets[i], sets[i + 1]);
+                    for set in sets.iter_mut() {
+                        if *set == replace {
+                            *set = keep;
+                        }
+                    }
+                    open_between(maze, (i, j), (i + 1, j));
+                }
+            }
+            if last_row {
+                break;
+            }
+            // Every set must extend down at least once.
+            let mut below: Vec<Option<usize>> = vec![None; cw];
+            let mut order: Vec<usize> = (0..cw).collect();
+            rng.shuffle(&mut order);
+            for &i in &order {
+                if !below.contains(&sets[i]) || rng.gen() {
+                    below[i] = sets[i];
+                    open_between(maze, (i, j), (i, j + 1));
+                }
+            }
+            sets = below;
+        }
+    }
+}
+
+impl MazeGenerator for AldousBroder {
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R) {
+        let cells = Cells::of(maze);
+        let mut visited = BitSet::new(cells.count());
+        let mut current = cells.random(rng);
+        visited.insert(cells.index(current));
+        open_cell(maze, current);
+        let mut remaining = cells.count() - 1;
+        while remaining > 0 {
+            let next = *rng.choose(&cells.neighbors(current)).unwrap();
+            if !visited.contains(cells.index(next)) {
+                visited.insert(cells.index(next));
+                open_between(maze, current, next);
+                remaining -= 1;
+            }
+            current = next;
+        }
+    }
+}
+
+impl MazeGenerator for Division {
+    fn carve<R: Rng>(&self, maze: &mut Maze, rng: &mut R) {
+        let cells = Cells::of(maze);
+        let (cw, ch) = (cells.width, cells.height);
+        for y in 1..2 * ch {
+            maze.open_run(y, 1, 2 * cw);
+        }
+        // Chambers are (x, y, w, h) in cell units.
+        let mut stack = vec![(0, 0, cw, ch)];
+        while let Some((cx, cy, cw, ch)) = stack.pop() {
+            if cw < 2 || ch < 2 {
+                continue;
+            }
+            let horizontal = if cw == ch { rng.gen() } else { ch > cw };
+            if horizontal {
+                let k = rng.gen_range(0, ch - 1);
+                let gap = rng.gen_range(cx, cx + cw);
+                let y = 2 * (cy + k + 1);
+                for x in 2 * cx + 1..2 * (cx + cw) {
+                    if x != 2 * gap + 1 {
+                        maze.set_closed(x, y);
+                    }
+                }
+                stack.push((cx, cy, cw, k + 1));
+                stack.push((cx, cy + k + 1, cw, ch - k - 1));
+            } else {
+                let k = rng.gen_range(0, cw - 1);
+                let gap = rng.gen_range(cy, cy + ch);
+                let x = 2 * (cx + k + 1);
+                for y in 2 * cy + 1..2 * (cy + ch) {
+                    if y != 2 * gap + 1 {
+                        maze.set_closed(x, y);
+                    }
+                }
+                stack.push((cx, cy, k + 1, ch));
+                stack.push((cx + k + 1, cy, cw - k - 1, ch));
+            }
+        }
+    }
+}
+
+/// Removes roughly `fraction` of the dead ends by knocking out one of their
+/// walls, preferring walls that also lead into another dead end.
+pub fn braid<R: Rng>(maze: &mut Maze, fraction: f64, rng: &mut R) {
+    let cells = Cells::of(maze);
+    let exits = |maze: &Maze, cell: Cell| {
+        cells.neighbors(cell)
+            .into_iter()
+            .filter(|&n| is_passage(maze, cell, n))
+            .count()
+    };
+    let mut dead_ends = vec![];
+    for j in 0..cells.height {
+        for i in 0..cells.width {
+            if exits(maze, (i, j)) == 1 {
+                dead_ends.push((i, j));
+            }
+        }
+    }
+    rng.shuffle(&mut dead_ends);
+    let count = (dead_ends.len() as f64 * fraction).round() as usize;
+    for &cell in dead_ends.iter().take(count) {
+        // An earlier removal may have already opened this one up.
+        if exits(maze, cell) != 1 {
+            continue;
+        }
+        let mut walls: Vec<Cell> = cells.neighbors(cell)
+            .into_iter()
+            .filter(|&n| !is_passage(maze, cell, n))
+            .collect();
+        rng.shuffle(&mut walls);
+        let target = walls.iter()
+            .find(|&&n| exits(maze, n) == 1)
+            .or(walls.first())
+            .cloned();
+        if let Some(next) = target {
+            open_between(maze, cell, next);
+        }
+    }
+}
+
+/// Knocks out up to `count` random interior walls, each creating a cycle.
+pub fn add_loops<R: Rng>(maze: &mut Maze, count: usize, rng: &mut R) {
+    let cells = Cells::of(maze);
+    let mut walls = vec![];
+    for j in 0..cells.height {
+        for i in 0..cells.width {
+            for n in cells.neighbors((i, j)) {
+                if cells.index(n) > cells.index((i, j)) && !is_passage(maze, (i, j), n) {
+                    walls.push(((i, j), n));
+                }
+            }
+        }
+    }
+    rng.shuffle(&mut walls);
+    for &(a, b) in walls.iter().take(count) {
+        open_between(maze, a, b);
+    }
+}
+
+/// Knocks out a random interior wall and then closes a random passage on the
+/// loop that made, so a perfect maze stays perfect but changes shape.
+pub fn mutate<R: Rng>(maze: &mut Maze, rng: &mut R) {
+    let cells = Cells::of(maze);
+    let mut walls = vec![];
+    for j in 0..cells.height {
+        for i in 0..cells.width {
+            for n in cells.neighbors((i, j)) {
+                if cells.index(n) > cells.index((i, j)) && !is_passage(maze, (i, j), n) {
+                    walls.push(((i, j), n));
+                }
+            }
+        }
+    }
+    let (a, b) = match rng.choose(&walls) {
+        Some(&wall) => wall,
+        None => return,
+    };
+    // the passages already joining a to b
+    let mut prior = HashMap::new();
+    prior.insert(a, a);
+    let mut stack = vec![a];
+    while let Some(current) = stack.pop() {
+        for n in cells.neighbors(current) {
+            if is_passage(maze, current, n) && !prior.contains_key(&n) {
+                prior.insert(n, current);
+                stack.push(n);
+            }
+        }
+    }
+    let mut route = vec![];
+    let mut at = b;
+    while prior.contains_key(&at) && at != a {
+        route.push((prior[&at], at));
+        at = prior[&at];
+    }
+    open_between(maze, a, b);
+    if let Some(&(c, d)) = rng.choose(&route) {
+        close_between(maze, c, d);
+    }
+}
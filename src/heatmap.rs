@@ -0,0 +1,33 @@
+use maze::Maze;
+use std::cmp::max;
+use std::collections::HashMap;
+
+/// xterm-256 background colours from yellow (expanded early) to red (late).
+const RAMP: [u8; 6] = [226, 220, 214, 208, 202, 196];
+
+/// Draws `maze` with each cell in `expanded` shaded by when it was first
+/// expanded, one string per row.
+pub fn ansi(maze: &Maze, expanded: &[(usize, usize)]) -> Vec<String> {
+    let mut order = HashMap::new();
+    for (i, &pos) in expanded.iter().enumerate() {
+        order.entry(pos).or_insert(i);
+    }
+    maze.render_with(|x, y, ch| match order.get(&(x, y)) {
+        Some(&i) => {
+            let shade = RAMP[i * RAMP.len() / expanded.len()];
+            format!("\x1b[30;48;5;{}m{}\x1b[0m", shade, ch)
+        }
+        None => ch.to_string(),
+    })
+}
+
+/// Prints two renderings of the same maze next to each other, under titles.
+pub fn print_side_by_side(maze: &Maze, left: (&str, Vec<String>), right: (&str, Vec<String>)) {
+    let column = max(maze.width(), left.0.chars().count()) + 4;
+    println!("{:<width$}{}", left.0, right.0, width = column);
+    // the escapes take no room on screen, so pad by the cell count
+    let padding = " ".repeat(column - maze.width());
+    for (l, r) in left.1.iter().zip(right.1.iter()) {
+        println!("{}{}{}", l, padding, r);
+    }
+}
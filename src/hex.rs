@@ -0,0 +1,219 @@
+use passages::{self, Passages};
+use rand::Rng;
+use search::Graph;
+use std::fmt;
+use std::io::BufRead;
+use stored::{LoadError, Stored};
+
+// Directions, clockwise from east. The opposite of `d` is `(d + 3) % 6`.
+const EAST: usize = 0;
+const SOUTH_EAST: usize = 1;
+const SOUTH_WEST: usize = 2;
+const DIRECTIONS: [char; 6] = ['→', '↘', '↙', '←', '↖', '↗'];
+
+/// A maze of pointy-topped hexagons in "odd-r" offset layout, where odd rows
+/// are shifted half a cell to the right.
+///
+/// The text format puts cell `(c, r)` at column `4c + 2(r % 2)` of line `2r`,
+/// written `o` (or `@`/`X` for start and goal). Passages are drawn between
+/// cells: `-` to the east on the same line, `\` and `/` to the south east and
+/// south west on the line below.
+///
+/// ```text
+/// @ - o   o
+///  \     / \
+///   o - o   X
+/// ```
+pub struct HexMaze {
+    width: usize,
+    height: usize,
+    // bit d set when the passage in direction d is open
+    passages: Vec<u8>,
+    marked: Vec<bool>,
+    start: (usize, usize),
+    goal: (usize, usize),
+}
+
+impl HexMaze {
+    pub fn new(width: usize, height: usize) -> HexMaze {
+        assert!(width > 0 && height > 0,
+                format!("mis-sized maze {}x{}", width, height));
+        HexMaze {
+            width: width,
+            height: height,
+            passages: vec![0; width * height],
+            marked: vec![false; width * height],
+            start: (0, 0),
+            goal: (width - 1, height - 1),
+        }
+    }
+
+    fn is_passage(&self, c: usize, r: usize, d: usize) -> bool {
+        self.passages[r * self.width + c] & (1 << d) != 0
+    }
+
+    pub fn random<R: Rng>(width: usize, height: usize, rng: &mut R) -> HexMaze {
+        let mut maze = Self::new(width, height);
+        passages::backtrack(&mut maze, rng);
+        maze
+    }
+
+    pub fn mark(&mut self, c: usize, r: usize) {
+        self.marked[r * self.width + c] = true;
+    }
+
+    pub fn is_marked(&self, c: usize, r: usize) -> bool {
+        self.marked[r * self.width + c]
+    }
+}
+
+impl Passages for HexMaze {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn adjacents(&self, c: usize, r: usize) -> Vec<(usize, (usize, usize))> {
+        let (c, r) = (c as isize, r as isize);
+        // odd rows are shifted right, so their diagonal neighbours are too
+        let shift = r % 2;
+        let offsets = [(1, 0),
+                       (shift, 1),
+                       (shift - 1, 1),
+                       (-1, 0),
+                       (shift - 1, -1),
+                       (shift, -1)];
+        offsets.iter()
+            .zip(0..)
+            .map(|(&(dc, dr), d)| (d, (c + dc, r + dr)))
+            .filter(|&(_, (nc, nr))| {
+                nc >= 0 && nr >= 0 && (nc as usize) < self.width && (nr as usize) < self.height
+            })
+            .map(|(d, (nc, nr))| (d, (nc as usize, nr as usize)))
+            .collect()
+    }
+
+    fn join(&mut self, c: usize, r: usize, d: usize, nc: usize, nr: usize) {
+        self.passages[r * self.width + c] |= 1 << d;
+        self.passages[nr * self.width + nc] |= 1 << (d + 3) % 6;
+    }
+}
+
+impl Stored for HexMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let text: Vec<String> = try!(reader.lines().collect());
+        let lines: Vec<Vec<char>> = text.iter().map(|l| l.chars().collect()).collect();
+        let h = (lines.len() + 1) / 2;
+        let w = lines.iter()
+            .zip(0..)
+            .filter(|&(_, y)| y % 2 == 0)
+            .map(|(l, y)| (l.len() + 3 - (y % 4)) / 4)
+            .max()
+            .unwrap_or(0);
+        if w == 0 {
+            return Err(LoadError::Invalid("empty map".to_string()));
+        }
+        let mut maze = HexMaze::new(w, h);
+        let at = |x: usize, y: usize| lines.get(y).and_then(|l| l.get(x)).cloned().unwrap_or(' ');
+        for r in 0..h {
+            for c in 0..w {
+                let (x, y) = (4 * c + 2 * (r % 2), 2 * r);
+                match at(x, y) {
+                    ' ' | 'o' => {}
+                    '@' => maze.start = (c, r),
+                    'X' => maze.goal = (c, r),
+                    ch => {
+                        return Err(LoadError::UnknownChar {
+                            ch: ch,
+                            line: y + 1,
+                            col: x + 1,
+                        })
+                    }
+                }
+                if at(x + 2, y) == '-' {
+                    try!(maze.load_passage(c, r, EAST, x + 2, y));
+                }
+                if at(x + 1, y + 1) == '\\' {
+                    try!(maze.load_passage(c, r, SOUTH_EAST, x + 1, y + 1));
+                }
+                if x > 0 && at(x - 1, y + 1) == '/' {
+                    try!(maze.load_passage(c, r, SOUTH_WEST, x - 1, y + 1));
+                }
+            }
+        }
+        Ok(maze)
+    }
+}
+
+impl Graph for HexMaze {
+    type Node = (usize, usize);
+    type Edge = char;
+
+    fn null_edge() -> char {
+        ' '
+    }
+
+    fn start(&self) -> (usize, usize) {
+        self.start
+    }
+
+    fn goal(&self) -> (usize, usize) {
+        self.goal
+    }
+
+    fn neighbors(&self, &(c, r): &(usize, usize)) -> Vec<(char, (usize, usize))> {
+        self.adjacents(c, r)
+            .into_iter()
+            .filter(|&(d, _)| self.is_passage(c, r, d))
+            .map(|(d, n)| (DIRECTIONS[d], n))
+            .collect()
+    }
+
+    fn distance(&self, &(c1, r1): &(usize, usize), &(c2, r2): &(usize, usize)) -> usize {
+        // convert to axial coordinates and take the hex distance
+        let q1 = c1 as isize - (r1 as isize - (r1 % 2) as isize) / 2;
+        let q2 = c2 as isize - (r2 as isize - (r2 % 2) as isize) / 2;
+        let (dq, dr) = (q2 - q1, r2 as isize - r1 as isize);
+        let d = ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as usize;
+        if d < 2 { d * 1000 } else { d * 1001 }
+    }
+}
+
+impl fmt::Display for HexMaze {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut canvas = vec![vec![' '; 4 * self.width + 2]; 2 * self.height - 1];
+        for r in 0..self.height {
+            for c in 0..self.width {
+                let (x, y) = (4 * c + 2 * (r % 2), 2 * r);
+                canvas[y][x] = if (c, r) == self.start {
+                    '@'
+                } else if (c, r) == self.goal {
+                    'X'
+                } else if self.is_marked(c, r) {
+                    '●'
+                } else {
+                    'o'
+                };
+                if self.is_passage(c, r, EAST) {
+                    let both = self.is_marked(c, r) && self.is_marked(c + 1, r);
+                    canvas[y][x + 2] = if both { '=' } else { '-' };
+                }
+                if self.is_passage(c, r, SOUTH_EAST) {
+                    canvas[y + 1][x + 1] = '\\';
+                }
+                if self.is_passage(c, r, SOUTH_WEST) {
+                    canvas[y + 1][x - 1] = '/';
+                }
+            }
+        }
+        for line in canvas {
+            let line: String = line.into_iter().collect();
+            try!(writeln!(f, "{}", line.trim_right()));
+        }
+        try!(f.write_str("\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use maze::Maze;
+use search::Graph;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::BufRead;
+use stored::{LoadError, Stored};
+
+/// The classic ice puzzle: open cells are ice, and a move slides until the
+/// next cell is a wall or the current one is ground. Ground is written `.`,
+/// and the start and goal are ground too.
+pub struct IceMaze {
+    maze: Maze,
+    ground: HashSet<(usize, usize)>,
+}
+
+impl Stored for IceMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let mut ground = HashSet::new();
+        let mut maze = try!(Maze::load_with(reader, |ch, x, y| {
+            match ch {
+                '.' => ground.insert((x, y)),
+                _ => return None,
+            };
+            Some(true)
+        }));
+        ground.insert(maze.start());
+        ground.insert(maze.goal());
+        // sliding around the world would never stop
+        maze.set_wrap(false);
+        Ok(IceMaze {
+            maze: maze,
+            ground: ground,
+        })
+    }
+}
+
+impl IceMaze {
+    fn step(&self, (x, y): (usize, usize), (dx, dy): (isize, isize)) -> Option<(usize, usize)> {
+        let (nx, ny) = (x as isize + dx, y as isize + dy);
+        if nx < 0 || ny < 0 || nx as usize >= self.maze.width() ||
+           ny as usize >= self.maze.height() || !self.maze.is_open(nx as usize, ny as usize) {
+            None
+        } else {
+            Some((nx as usize, ny as usize))
+        }
+    }
+
+    /// Where a move from `from` in direction (dx, dy) comes to rest.
+    fn slide(&self, from: (usize, usize), dir: (isize, isize)) -> (usize, usize) {
+        let mut at = from;
+        while let Some(next) = self.step(at, dir) {
+            at = next;
+            if self.ground.contains(&at) {
+                break;
+            }
+        }
+        at
+    }
+
+    /// Marks every cell passed over sliding from `from` to `to`.
+    pub fn mark(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let (mut x, mut y) = from;
+        self.maze.mark(x, y);
+        while (x, y) != to {
+            x = if x < to.0 { x + 1 } else if x > to.0 { x - 1 } else { x };
+            y = if y < to.1 { y + 1 } else if y > to.1 { y - 1 } else { y };
+            self.maze.mark(x, y);
+        }
+    }
+}
+
+impl Graph for IceMaze {
+    type Node = (usize, usize);
+    type Edge = char;
+
+    fn null_edge() -> char {
+        ' '
+    }
+
+    fn start(&self) -> (usize, usize) {
+        self.maze.start()
+    }
+
+    fn goal(&self) -> (usize, usize) {
+        self.maze.goal()
+    }
+
+    fn neighbors(&self, &pos: &(usize, usize)) -> Vec<(char, (usize, usize))> {
+        [('<', (-1, 0)), ('^', (0, -1)), ('>', (1, 0)), ('v', (0, 1))]
+            .iter()
+            .map(|&(dir, d)| (dir, self.slide(pos, d)))
+            .filter(|&(_, rest)| rest != pos)
+            .collect()
+    }
+
+    fn distance(&self, &(x1, y1): &(usize, usize), &(x2, y2): &(usize, usize)) -> usize {
+        // every move is one push however far it slides, and changes one axis
+        let moves = (x1 != x2) as usize + (y1 != y2) as usize;
+        moves * 1000
+    }
+}
+
+impl fmt::Display for IceMaze {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self.maze.render_with(|x, y, ch| {
+            if ch == ' ' && self.ground.contains(&(x, y)) { '.' } else { ch }
+        });
+        for row in rows {
+            try!(writeln!(f, "{}", row));
+        }
+        Ok(())
+    }
+}
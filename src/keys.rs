@@ -0,0 +1,117 @@
+use maze::Maze;
+use search::Graph;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use stored::{LoadError, Stored};
+
+/// A maze where lowercase letters are keys and the matching uppercase letters
+/// are doors that only open once their key is held. `x` is never a key, since
+/// `X` already marks the goal.
+pub struct KeyMaze {
+    maze: Maze,
+    keys: HashMap<(usize, usize), u8>,
+    doors: HashMap<(usize, usize), u8>,
+    // keys in the order they were picked up along the marked path
+    picked: Vec<u8>,
+}
+
+fn letter(index: u8) -> char {
+    (b'a' + index) as char
+}
+
+impl Stored for KeyMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let mut keys = HashMap::new();
+        let mut doors = HashMap::new();
+        let maze = try!(Maze::load_with(reader, |ch, x, y| {
+            match ch {
+                'a'...'z' if ch != 'x' => {
+                    keys.insert((x, y), ch as u8 - b'a');
+                }
+                'A'...'Z' => {
+                    doors.insert((x, y), ch as u8 - b'A');
+                }
+                _ => return None,
+            }
+            Some(true)
+        }));
+        Ok(KeyMaze {
+            maze: maze,
+            keys: keys,
+            doors: doors,
+            picked: vec![],
+        })
+    }
+}
+
+impl KeyMaze {
+    pub fn mark(&mut self, &((x, y), held): &((usize, usize), u32)) {
+        self.maze.mark(x, y);
+        if let Some(&k) = self.keys.get(&(x, y)) {
+            if held & (1 << k) != 0 && !self.picked.contains(&k) {
+                self.picked.push(k);
+            }
+        }
+    }
+}
+
+impl Graph for KeyMaze {
+    // position and the set of keys held
+    type Node = ((usize, usize), u32);
+    type Edge = char;
+
+    fn null_edge() -> char {
+        ' '
+    }
+
+    fn start(&self) -> ((usize, usize), u32) {
+        (self.maze.start(), 0)
+    }
+
+    fn goal(&self) -> ((usize, usize), u32) {
+        // keys no longer matter once we're out, like snake's empty exit state
+        (self.maze.goal(), 0)
+    }
+
+    fn neighbors(&self, &(pos, held): &((usize, usize), u32)) -> Vec<(char, ((usize, usize), u32))> {
+        let goal = self.maze.goal();
+        self.maze
+            .neighbors(&pos)
+            .into_iter()
+            .filter(|&(_, next)| self.doors.get(&next).map_or(true, |&d| held & (1 << d) != 0))
+            .map(|(dir, next)| {
+                let held = if next == goal {
+                    0
+                } else {
+                    held | self.keys.get(&next).map_or(0, |&k| 1 << k)
+                };
+                (dir, (next, held))
+            })
+            .collect()
+    }
+
+    fn distance(&self, &(a, _): &((usize, usize), u32), &(b, _): &((usize, usize), u32)) -> usize {
+        self.maze.distance(&a, &b)
+    }
+}
+
+impl fmt::Display for KeyMaze {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self.maze.render_with(|x, y, ch| if let Some(&k) = self.keys.get(&(x, y)) {
+            letter(k)
+        } else if let Some(&d) = self.doors.get(&(x, y)) {
+            letter(d).to_ascii_uppercase()
+        } else {
+            ch
+        });
+        for row in rows {
+            try!(writeln!(f, "{}", row));
+        }
+        if !self.picked.is_empty() {
+            let picked: Vec<String> = self.picked.iter().map(|&k| letter(k).to_string()).collect();
+            try!(writeln!(f, "Keys picked up: {}", picked.join(", ")));
+        }
+        Ok(())
+    }
+}
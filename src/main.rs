@@ -1,17 +1,52 @@
 extern crate getopts;
 extern crate n_array;
 extern crate rand;
+mod agents;
+mod bits;
+mod difficulty;
+mod generator;
+mod heatmap;
+mod hex;
+mod ice;
+mod keys;
 mod maze;
+mod maze3d;
+mod mdp;
+mod passages;
 mod snake;
+mod pnm;
 mod search;
 use search::Graph;
+mod stats;
 mod stored;
+mod svg;
+mod timed;
+mod thin;
+mod tour;
+mod triangle;
+mod walkers;
+use agents::AgentMaze;
+use difficulty::Target;
+use generator::MazeGenerator;
 use getopts::{Options, HasArg, Occur};
-use maze::Maze;
+use hex::HexMaze;
+use ice::IceMaze;
+use keys::KeyMaze;
+use maze::{CornerCutting, Maze};
+use maze3d::Maze3d;
+use pnm::Pnm;
+use mdp::{Mdp, Method};
+use rand::{Rng, SeedableRng, StdRng};
 use std::env;
 use std::fs::File;
-use std::io::BufReader;
-use stored::Stored;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::process;
+use stored::{LoadError, Stored};
+use timed::TimedMaze;
+use thin::ThinMaze;
+use tour::TourMaze;
+use triangle::TriMaze;
+use walkers::Walker;
 
 // fn main() {
 // let (w, h) = (10, 10);
@@ -23,6 +58,7 @@ use stored::Stored;
 // }
 //
 
+#[derive(Clone, Copy)]
 enum Strategy {
     DFS,
     AStar,
@@ -34,11 +70,354 @@ fn solve<G: Graph>(graph: &G, strat: Strategy) -> Option<Vec<(G::Edge, G::Node)>
         Strategy::AStar => search::a_star_search(graph),
     }
 }
-fn maze(input: Option<String>, strat: Strategy) {
+
+fn solve_traced<G: Graph>(graph: &G, strat: Strategy) -> (Option<Vec<(G::Edge, G::Node)>>, Vec<G::Node>) {
+    match strat {
+        Strategy::DFS => search::dfs_traced(graph),
+        Strategy::AStar => search::a_star_traced(graph),
+    }
+}
+#[derive(Clone, Copy)]
+enum Generation {
+    Backtracker,
+    Prim,
+    Kruskal,
+    Wilson,
+    Eller,
+    AldousBroder,
+    Division,
+}
+
+fn generate<R: Rng>(opts: &MazeOptions, rng: &mut R) -> Maze {
+    let (w, h, wrap) = (opts.width, opts.height, opts.wrap);
+    match opts.gen {
+        Generation::Backtracker => generator::Backtracker.generate(w, h, wrap, rng),
+        Generation::Prim => generator::Prim.generate(w, h, wrap, rng),
+        Generation::Kruskal => generator::Kruskal.generate(w, h, wrap, rng),
+        Generation::Wilson => generator::Wilson.generate(w, h, wrap, rng),
+        Generation::Eller => generator::Eller.generate(w, h, wrap, rng),
+        Generation::AldousBroder => generator::AldousBroder.generate(w, h, wrap, rng),
+        Generation::Division => generator::Division.generate(w, h, wrap, rng),
+    }
+}
+
+fn seeded_rng(seed: Option<usize>) -> StdRng {
+    let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!("Seed: {}", seed);
+    SeedableRng::from_seed(&[seed][..])
+}
+
+struct MazeOptions {
+    gen: Generation,
+    width: usize,
+    height: usize,
+    seed: Option<usize>,
+    braid: f64,
+    loops: usize,
+    diagonals: Option<CornerCutting>,
+    floors: usize,
+    wrap: bool,
+    target: Target,
+}
+
+#[derive(Clone, Copy)]
+enum Render {
+    Ppm,
+    Svg,
+}
+
+/// What to report or save about a solved maze.
+struct OutputOptions {
+    heatmap: bool,
+    compare: bool,
+    render: Option<(Render, String)>,
+    scale: Option<usize>,
+}
+
+/// Prints why `file` couldn't be loaded and exits.
+fn fail(file: &str, err: LoadError) -> ! {
+    let _ = writeln!(io::stderr(), "{}: {}", file, err);
+    process::exit(1);
+}
+
+/// Loads a puzzle from `file`, or exits with a diagnostic if it can't.
+fn load<T: Stored>(file: &str) -> T {
+    let loaded = File::open(file)
+        .map_err(LoadError::from)
+        .and_then(|f| T::load(&mut BufReader::new(f)));
+    loaded.unwrap_or_else(|err| fail(file, err))
+}
+
+fn load_maze(input: Option<String>, opts: &MazeOptions) -> Maze {
+    let mut m = if let Some(file) = input {
+        if file.ends_with(".pbm") || file.ends_with(".pgm") {
+            let mut m = load::<Pnm>(&file).0;
+            let sidecar = format!("{}.points", file);
+            if let Ok(points) = File::open(&sidecar) {
+                if let Err(err) = pnm::load_points(&mut m, &mut BufReader::new(points)) {
+                    fail(&sidecar, err);
+                }
+            }
+            m
+        } else {
+            load(&file)
+        }
+    } else if opts.target.is_set() {
+        let mut rng = seeded_rng(opts.seed);
+        let scored = difficulty::generate(&opts.target, |rng| generate(opts, rng), &mut rng)
+            .expect("Couldn't generate a maze meeting the target");
+        println!("Length: {}, decision points: {}, DFS expansions: {}",
+                 scored.stats.solution_length.unwrap(),
+                 scored.stats.decision_points,
+                 scored.expansions);
+        scored.maze
+    } else {
+        let mut rng = seeded_rng(opts.seed);
+        let mut m = generate(opts, &mut rng);
+        generator::braid(&mut m, opts.braid, &mut rng);
+        generator::add_loops(&mut m, opts.loops, &mut rng);
+        m
+    };
+    m.set_diagonals(opts.diagonals);
+    if opts.wrap {
+        m.set_wrap(true);
+    }
+    m
+}
+
+fn maze(input: Option<String>,
+        strat: Strategy,
+        walker: Option<Walker>,
+        opts: MazeOptions,
+        out: OutputOptions) {
+    let mut m = load_maze(input, &opts);
+    if out.compare {
+        return compare(m, out);
+    }
+    if let Some(walker) = walker {
+        return walk(m, walker, out);
+    }
+    let (result, expanded) = if out.heatmap {
+        let (result, expanded) = solve_traced(&m, strat);
+        println!("Expanded: {}", expanded.len());
+        (result, expanded)
+    } else {
+        (solve(&m, strat), vec![])
+    };
+    let mut path = vec![];
+    if let Some(solution) = result {
+        m.mark_path(&solution);
+        path.extend(solution.into_iter().map(|(_, pos)| pos));
+        if out.heatmap {
+            println!("Maze:\n{}\n", heatmap::ansi(&m, &expanded).join("\n"));
+        } else {
+            println!("Maze:\n{}", &m);
+        }
+    } else {
+        println!("No solution!\n");
+    }
+    let heat = if out.heatmap { Some(&expanded[..]) } else { None };
+    save_render(&m, &path, heat, &out);
+}
+
+fn save_render(m: &Maze,
+               path: &[(usize, usize)],
+               heat: Option<&[(usize, usize)]>,
+               out: &OutputOptions) {
+    if let Some((render, ref file)) = out.render {
+        let mut writer = BufWriter::new(File::create(file).expect("Couldn't create file"));
+        match render {
+            Render::Ppm => pnm::save_ppm(m, out.scale.unwrap_or(1), &mut writer),
+            Render::Svg => svg::render(m, path, heat, out.scale.unwrap_or(10), &mut writer),
+        }
+        .expect("Couldn't write image");
+    }
+}
+
+/// Solves `m` by walking it, showing every cell the walker stepped on.
+fn walk(mut m: Maze, walker: Walker, out: OutputOptions) {
+    let walk = walkers::walk(&m, walker);
+    println!("Steps: {}", walk.steps);
+    println!("Reached the goal: {}", if walk.solved { "yes" } else { "no" });
+    for &(x, y) in &walk.route {
+        m.mark(x, y);
+    }
+    println!("Maze:\n{}", &m);
+    save_render(&m, &walk.route, None, &out);
+}
+
+/// Solves `m` with both strategies and shows what each expanded side by side.
+fn compare(m: Maze, out: OutputOptions) {
+    let mut runs = vec![];
+    for &(name, strat) in &[("DFS", Strategy::DFS), ("A*", Strategy::AStar)] {
+        let (result, expanded) = solve_traced(&m, strat);
+        let solution = result.unwrap_or(vec![]);
+        let mut marked = m.clone();
+        marked.mark_path(&solution);
+        let mut path: Vec<(usize, usize)> = solution.into_iter().map(|(_, pos)| pos).collect();
+        // dfs paths leave out the start, a* paths don't
+        if !path.is_empty() && path[0] != m.start() {
+            path.insert(0, m.start());
+        }
+        let title = format!("{}: {} expanded, length {}", name, expanded.len(), path.len());
+        runs.push((title, marked, path, expanded));
+    }
+    let (ref dfs, ref a_star) = (&runs[0], &runs[1]);
+    heatmap::print_side_by_side(&m,
+                                (&dfs.0, heatmap::ansi(&dfs.1, &dfs.3)),
+                                (&a_star.0, heatmap::ansi(&a_star.1, &a_star.3)));
+    // main only lets comparisons render as svg
+    if let Some((Render::Svg, file)) = out.render {
+        let mut writer = BufWriter::new(File::create(file).expect("Couldn't create file"));
+        svg::render_pair(&m,
+                         (&dfs.0, &dfs.2, &dfs.3),
+                         (&a_star.0, &a_star.2, &a_star.3),
+                         out.scale.unwrap_or(10),
+                         &mut writer)
+            .expect("Couldn't write image");
+    }
+}
+
+/// Prints structure and difficulty measures of a loaded or generated maze.
+fn stats(input: Option<String>, opts: MazeOptions) {
+    let m = load_maze(input, &opts);
+    println!("{}", stats::analyze(&m));
+}
+
+/// Generates a maze from `opts` and saves it in the text format to `file`.
+fn save_generated(opts: MazeOptions, file: String) {
+    let m = load_maze(None, &opts);
+    println!("Maze:\n{}", &m);
+    let mut writer = BufWriter::new(File::create(&file).expect("Couldn't create file"));
+    m.save(&mut writer).expect("Couldn't save maze");
+    println!("Saved to {}", file);
+}
+
+fn mdp(input: Option<String>, opts: MazeOptions, slip: f64, method: Method) {
+    let m = load_maze(input, &opts);
+    let plan = Mdp::new(&m, slip).solve(method);
+    println!("Iterations: {}", plan.iterations);
+    match plan.cost.get(&m.start()) {
+        Some(cost) => println!("Expected cost: {:.2}", cost),
+        None => println!("No solution!\n"),
+    }
+    println!("Policy:\n{}", &plan);
+}
+
+fn maze3d(input: Option<String>, strat: Strategy, opts: MazeOptions) {
+    let mut m = if let Some(file) = input {
+        load::<Maze3d>(&file)
+    } else {
+        let mut rng = seeded_rng(opts.seed);
+        let floors = (0..opts.floors)
+            .map(|_| generate(&opts, &mut rng))
+            .collect();
+        Maze3d::stack(floors, &mut rng)
+    };
+    let result = solve(&m, strat);
+    if let Some(solution) = result {
+        for (_, (x, y, z)) in solution {
+            m.mark(x, y, z);
+        }
+        println!("Maze:\n{}", &m);
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn keys(input: Option<String>, strat: Strategy) {
+    let mut m = load::<KeyMaze>(&input.expect("Key mazes require input files"));
+    let result = solve(&m, strat);
+    if let Some(solution) = result {
+        for (_, node) in solution {
+            m.mark(&node);
+        }
+        println!("Maze:\n{}", &m);
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn ice(input: Option<String>, strat: Strategy) {
+    let mut m = load::<IceMaze>(&input.expect("Ice puzzles require input files"));
+    let result = solve(&m, strat);
+    if let Some(solution) = result {
+        println!("Length: {}", solution.len());
+        let mut at = m.start();
+        for (edge, pos) in solution {
+            print!("{}", edge);
+            m.mark(at, pos);
+            at = pos;
+        }
+        println!("\nMaze:\n{}", &m);
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn timed(input: Option<String>, strat: Strategy) {
+    let m = load::<TimedMaze>(&input.expect("Timed mazes require input files"));
+    let result = solve(&m, strat);
+    if let Some(solution) = result {
+        println!("Length: {}", solution.len());
+        let moves: String = solution.iter().map(|&(edge, _)| edge).collect();
+        println!("{}\n", moves.trim());
+        // paths may leave out the start, so go by each step's own tick; the
+        // goal is stored at tick 0 since it doesn't matter when we get there
+        let mut tick = 0;
+        for (_, (pos, t)) in solution {
+            tick = if (pos, t) == m.goal() { tick + 1 } else { t };
+            m.print(pos, tick);
+        }
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn agents(input: Option<String>) {
+    let m = load::<AgentMaze>(&input.expect("Multi-agent mazes require input files"));
+    let baseline = m.prioritized();
+    match baseline {
+        Some(ref plan) => println!("Prioritized: cost {}", plan.cost),
+        None => println!("Prioritized: no solution"),
+    }
+    let best = m.cbs();
+    match best {
+        Some(ref plan) => println!("CBS: cost {}, {} searches", plan.cost, plan.searches),
+        None => println!("CBS: no solution"),
+    }
+    if let Some(plan) = best.or(baseline) {
+        let ticks = plan.paths.iter().map(|p| p.len()).max().unwrap();
+        println!("");
+        for t in 0..ticks {
+            m.print(&plan, t);
+        }
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn tour(input: Option<String>) {
+    let mut m = load::<TourMaze>(&input.expect("Tours require input files"));
+    if let Some(tour) = m.solve() {
+        println!("Order: {:?}", tour.order);
+        // costs are kept in thousandths of a step
+        println!("Cost: {}", tour.cost as f64 / 1000.0);
+        println!("Length: {}", tour.route.len());
+        for (_, (x, y)) in tour.route {
+            m.mark(x, y);
+        }
+        println!("Maze:\n{}", &m);
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn thin(input: Option<String>, strat: Strategy, opts: MazeOptions) {
     let mut m = if let Some(file) = input {
-        Maze::load(&mut BufReader::new(&mut File::open(file).expect("Couldn't open file")))
+        load::<ThinMaze>(&file)
     } else {
-        Maze::random(41, 41)
+        ThinMaze::from_block(&load_maze(None, &opts))
     };
     let result = solve(&m, strat);
     if let Some(solution) = result {
@@ -46,16 +425,48 @@ fn maze(input: Option<String>, strat: Strategy) {
             m.mark(x, y);
         }
         println!("Maze:\n{}", &m);
+        println!("Blocks:\n{}", m.to_block());
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn hex(input: Option<String>, strat: Strategy, opts: MazeOptions) {
+    let mut m = if let Some(file) = input {
+        load::<HexMaze>(&file)
+    } else {
+        HexMaze::random(opts.width, opts.height, &mut seeded_rng(opts.seed))
+    };
+    let result = solve(&m, strat);
+    if let Some(solution) = result {
+        for (_, (c, r)) in solution {
+            m.mark(c, r);
+        }
+        println!("Maze:\n{}", &m);
+    } else {
+        println!("No solution!\n");
+    }
+}
+
+fn triangle(input: Option<String>, strat: Strategy, opts: MazeOptions) {
+    let mut m = if let Some(file) = input {
+        load::<TriMaze>(&file)
+    } else {
+        TriMaze::random(opts.width, opts.height, &mut seeded_rng(opts.seed))
+    };
+    let result = solve(&m, strat);
+    if let Some(solution) = result {
+        for (_, (c, r)) in solution {
+            m.mark(c, r);
+        }
+        println!("Maze:\n{}", &m);
     } else {
         println!("No solution!\n");
     }
 }
 
 fn snake(input: Option<String>, strat: Strategy, cool_mode: bool) {
-    let m =
-        snake::Level::load(&mut BufReader::new(
-                &mut File::open(input.expect("Snakebird requires input files"))
-                .expect("Couldn't open file")));
+    let m = load::<snake::Level>(&input.expect("Snakebird requires input files"));
     let (x, y) = m.initial_snake[0];
     let result = solve(&m, strat);
     if let Some(solution) = result {
@@ -80,7 +491,8 @@ fn snake(input: Option<String>, strat: Strategy, cool_mode: bool) {
 
 fn main() {
     let mut opts = Options::new();
-    let brief = "Solves puzzles.";
+    let brief = "Solves puzzles, or with 'generate', saves a random maze to --out, or with \
+                 'stats', describes a maze.";
 
     opts.opt("i",
              "input",
@@ -90,16 +502,130 @@ fn main() {
              Occur::Optional);
     opts.opt("t",
              "type",
-             "Puzzle type. Either 'snake' or 'maze'.",
+             "Puzzle type. One of 'maze', 'maze3d', 'keys', 'tour', 'ice', 'timed', \
+              'mdp', 'agents', 'thin', 'hex', 'tri' or 'snake'.",
              "TYPE",
              HasArg::Yes,
              Occur::Optional);
     opts.opt("s",
              "strategy",
-             "Strategy. Either 'dfs' or 'astar'.",
+             "Strategy. Either 'dfs' or 'a_star'.",
              "TYPE",
              HasArg::Yes,
              Occur::Optional);
+    opts.opt("",
+             "walker",
+             "Solve a plain maze by walking it instead of searching. One of 'left_hand', \
+              'right_hand', 'pledge', 'tremaux' or 'dead_end_filling'.",
+             "WALKER",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("g",
+             "generator",
+             "Random maze generator. One of 'backtracker', 'prim', 'kruskal', 'wilson', \
+              'eller', 'aldous_broder' or 'division'.",
+             "GENERATOR",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "seed",
+             "Seed for random maze generation.",
+             "SEED",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("w",
+             "width",
+             "Width of a random maze. Must be odd.",
+             "WIDTH",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("h",
+             "height",
+             "Height of a random maze. Must be odd.",
+             "HEIGHT",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("b",
+             "braid",
+             "Fraction of dead ends to remove from a random maze, from 0 to 1.",
+             "FRACTION",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("l",
+             "loops",
+             "Number of extra walls to knock out of a random maze.",
+             "LOOPS",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "floors",
+             "Number of floors in a random 3D maze.",
+             "FLOORS",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("d",
+             "diagonal",
+             "Allow diagonal maze moves. Corner cutting is 'never', 'one_wall' or 'always'.",
+             "CORNERS",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "slip",
+             "Chance that a move on a slippery maze ('mdp') slides sideways instead.",
+             "PROBABILITY",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "method",
+             "How to solve a slippery maze. One of 'value' or 'policy' iteration.",
+             "METHOD",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.optflag("",
+                 "wrap",
+                 "Make the maze toroidal. Random toroidal mazes need an even width and height.");
+    opts.opt("",
+             "min_length",
+             "Keep generating until the solution takes at least this many steps.",
+             "STEPS",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "min_decisions",
+             "Keep generating until the solution passes at least this many junctions.",
+             "COUNT",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "min_expansions",
+             "Keep generating until depth-first search expands at least this many cells.",
+             "COUNT",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "render",
+             "Save the solved maze as an image to --out. One of 'ppm' or 'svg'.",
+             "FORMAT",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "out",
+             "File to save a rendered or generated maze to.",
+             "FILE",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.opt("",
+             "scale",
+             "Pixels per maze cell in a saved image. Defaults to 1 for 'ppm' and 10 for 'svg'.",
+             "PIXELS",
+             HasArg::Yes,
+             Occur::Optional);
+    opts.optflag("",
+                 "heatmap",
+                 "Shade the cells the search expanded, from yellow (first) to red (last).");
+    opts.optflag("",
+                 "compare",
+                 "Solve with both DFS and A* and show what each expanded side by side.");
     opts.optflag("c", "cool_mode", "Whether to print like a cool person");
     let m = match opts.parse(env::args()) {
         Ok(m) => m,
@@ -114,9 +640,113 @@ fn main() {
         "a_star" => Strategy::AStar,
         other => panic!("Unexpected strategy: {}", other),
     };
+    let walker = m.opt_str("walker").map(|walker| match walker.as_str() {
+        "left_hand" => Walker::LeftHand,
+        "right_hand" => Walker::RightHand,
+        "pledge" => Walker::Pledge,
+        "tremaux" => Walker::Tremaux,
+        "dead_end_filling" => Walker::DeadEndFilling,
+        other => panic!("Unexpected walker: {}", other),
+    });
+    let gen = match m.opt_str("generator").as_ref().map(|s| s.as_str()).unwrap_or("backtracker") {
+        "backtracker" => Generation::Backtracker,
+        "prim" => Generation::Prim,
+        "kruskal" => Generation::Kruskal,
+        "wilson" => Generation::Wilson,
+        "eller" => Generation::Eller,
+        "aldous_broder" => Generation::AldousBroder,
+        "division" => Generation::Division,
+        other => panic!("Unexpected generator: {}", other),
+    };
+    let seed = m.opt_str("seed").map(|s| s.parse().expect("Seed must be a number"));
+    let width = m.opt_str("width").map_or(41, |s| s.parse().expect("Width must be a number"));
+    let height = m.opt_str("height").map_or(41, |s| s.parse().expect("Height must be a number"));
+    let braid = m.opt_str("braid").map_or(0.0, |s| s.parse().expect("Braid must be a number"));
+    let loops = m.opt_str("loops").map_or(0, |s| s.parse().expect("Loops must be a number"));
+    let diagonals = match m.opt_str("diagonal").as_ref().map(|s| s.as_str()) {
+        None => None,
+        Some("never") => Some(CornerCutting::Never),
+        Some("one_wall") => Some(CornerCutting::OneWall),
+        Some("always") => Some(CornerCutting::Always),
+        Some(other) => panic!("Unexpected corner cutting: {}", other),
+    };
+    let slip = m.opt_str("slip").map_or(0.2, |s| s.parse().expect("Slip must be a number"));
+    let method = match m.opt_str("method").as_ref().map(|s| s.as_str()).unwrap_or("value") {
+        "value" => Method::ValueIteration,
+        "policy" => Method::PolicyIteration,
+        other => panic!("Unexpected method: {}", other),
+    };
+    let floors = m.opt_str("floors").map_or(3, |s| s.parse().expect("Floors must be a number"));
+    let target = Target {
+        min_length: m.opt_str("min_length")
+            .map_or(0, |s| s.parse().expect("Minimum length must be a number")),
+        min_decisions: m.opt_str("min_decisions")
+            .map_or(0, |s| s.parse().expect("Minimum decisions must be a number")),
+        min_expansions: m.opt_str("min_expansions")
+            .map_or(0, |s| s.parse().expect("Minimum expansions must be a number")),
+    };
+    let maze_opts = MazeOptions {
+        gen: gen,
+        width: width,
+        height: height,
+        seed: seed,
+        braid: braid,
+        loops: loops,
+        diagonals: diagonals,
+        floors: floors,
+        wrap: m.opt_present("wrap"),
+        target: target,
+    };
+    let render = m.opt_str("render").map(|format| {
+        let render = match format.as_str() {
+            "ppm" => Render::Ppm,
+            "svg" => Render::Svg,
+            other => panic!("Unexpected render format: {}", other),
+        };
+        (render, m.opt_str("out").expect("Rendering needs an --out file"))
+    });
+    let out = OutputOptions {
+        heatmap: m.opt_present("heatmap"),
+        compare: m.opt_present("compare"),
+        render: render,
+        scale: m.opt_str("scale").map(|s| s.parse().expect("Scale must be a number")),
+    };
+    if let Some((Render::Ppm, _)) = out.render {
+        if out.compare {
+            println!("{}\n\nComparisons only render as svg\n", opts.usage(brief));
+            return;
+        }
+    }
+    if m.free.get(1).map(|s| s.as_str()) == Some("generate") {
+        return save_generated(maze_opts, m.opt_str("out").expect("Generating needs an --out file"));
+    }
     let input = m.opt_str("input");
-    match m.opt_str("type").as_ref().map(|s| s.as_str()).unwrap_or("maze") {
-        "maze" => maze(input, strat),
+    if m.free.get(1).map(|s| s.as_str()) == Some("stats") {
+        return stats(input, maze_opts);
+    }
+    let kind = m.opt_str("type").unwrap_or("maze".to_string());
+    if walker.is_some() && kind != "maze" {
+        println!("{}\n\nOnly plain mazes can be walked, not '{}'\n",
+                 opts.usage(brief),
+                 kind);
+        return;
+    }
+    if kind == "thin" && maze_opts.wrap && input.is_none() {
+        println!("{}\n\nGenerated thin mazes can't wrap\n", opts.usage(brief));
+        return;
+    }
+    match kind.as_str() {
+        "maze" => maze(input, strat, walker, maze_opts, out),
+        "maze3d" => maze3d(input, strat, maze_opts),
+        "keys" => keys(input, strat),
+        "tour" => tour(input),
+        "ice" => ice(input, strat),
+        "timed" => timed(input, strat),
+        "mdp" => mdp(input, maze_opts, slip, method),
+        "agents" => agents(input),
+        "thin" => thin(input, strat, maze_opts),
+        "hex" => hex(input, strat, maze_opts),
+        "tri" => triangle(input, strat, maze_opts),
         "snake" => snake(input, strat, cool_mode),
         other => {
             println!("{}\n\nUnexpected puzzle type: '{}'\n",
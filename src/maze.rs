@@ -1,50 +1,88 @@
 extern crate rand;
+use bits::BitGrid;
 use rand::Rng;
 use search::Graph;
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::io::BufRead;
-use stored::Stored;
+use std::io::{self, BufRead, Write};
+use stored::{LoadError, Stored};
 
+/// Terrain characters and the cost of stepping onto them, in thousandths of
+/// a plain step. Index 0 is plain floor.
+const TERRAIN: [(char, usize); 4] = [(' ', 1000), ('=', 500), ('%', 3000), ('~', 5000)];
+
+/// Cost of a diagonal step, in thousandths of a straight one.
+const DIAGONAL: usize = 1414;
+
+/// Cost of jumping between a pair of portals, in thousandths of a step.
+const TELEPORT: usize = 1000;
+
+/// Which corners a diagonal move may cut in eight-connected mode.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CornerCutting {
+    /// Both orthogonal cells beside the move must be open.
+    Never,
+    /// At most one orthogonal cell beside the move may be a wall.
+    OneWall,
+    /// The move may squeeze between two walls.
+    Always,
+}
+
+#[derive(Clone)]
 pub struct Maze {
     width: usize,
     height: usize,
-    open: Vec<bool>,
-    marked: Vec<bool>,
+    open: BitGrid,
+    marked: BitGrid,
+    // empty until some cell gets terrain, since most mazes have none
+    terrain: Vec<u8>,
+    // cheapest step anywhere in the maze, to keep the heuristic admissible
+    min_cost: usize,
+    diagonals: Option<CornerCutting>,
+    // each portal cell maps to its partner and the digit drawn for both
+    portals: HashMap<(usize, usize), ((usize, usize), char)>,
+    // portal cells a marked path jumps between
+    jumped: HashSet<(usize, usize)>,
+    // toroidal: stepping off one edge enters the opposite one
+    wrap: bool,
     start: (usize, usize),
     goal: (usize, usize),
 }
 
 impl Stored for Maze {
-    fn load<R: BufRead>(reader: &mut R) -> Self {
-        let lines: Vec<String> = reader.lines().map(|l| l.ok().unwrap()).collect();
-
-        let w = lines.iter().map(|ref l| l.len()).max().unwrap();
-        let h = lines.len();
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        Maze::load_with(reader, |_, _, _| None)
+    }
 
-        let mut maze = Maze::new(w, h);
-        for (line, y) in lines.iter().zip(0..h) {
-            for (ch, x) in line.chars().zip(0..w) {
-                let open = match ch {
-                    ' ' => true,
-                    '#' => false,
-                    '@' => {
-                        maze.start = (x, y);
-                        true
-                    }
-                    'X' => {
-                        maze.goal = (x, y);
-                        true
-                    }
-                    _ => {
-                        panic!("Unexpected char: '{}'", ch);
-                    }
-                };
-                if open {
-                    maze.set_open(x, y);
-                }
+    fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        // a marker on a wall would load back as an open cell
+        for &(what, (x, y)) in &[("start", self.start), ("goal", self.goal)] {
+            if !self.in_bounds(x, y) || !self.is_open(x, y) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("the {} at ({}, {}) is on a wall", what, x, y)));
             }
         }
-        maze
+        if self.wrap {
+            try!(writeln!(writer, "!wrap"));
+        }
+        for y in 0..self.height {
+            let line: String = (0..self.width)
+                .map(|x| if (x, y) == self.start {
+                    '@'
+                } else if (x, y) == self.goal {
+                    'X'
+                } else if let Some(&(_, label)) = self.portals.get(&(x, y)) {
+                    label
+                } else if !self.is_open(x, y) {
+                    '#'
+                } else {
+                    self.terrain_char(x, y)
+                })
+                .collect();
+            try!(writeln!(writer, "{}", line));
+        }
+        Ok(())
     }
 }
 
@@ -65,21 +103,52 @@ impl Graph for Maze {
     }
 
     fn neighbors(&self, &(x, y): &(usize, usize)) -> Vec<(char, (usize, usize))> {
-        self.adjacents(x, y)
+        // every neighbour at once, unless they might be across a wrapped edge
+        let around = if self.wrap { None } else { Some(self.open.around(x, y)) };
+        let is_open = |nx: usize, ny: usize| match around {
+            Some(bits) => bits & (1 << ((ny + 1 - y) * 3 + nx + 1 - x)) != 0,
+            None => self.is_open(nx, ny),
+        };
+        let mut list: Vec<(char, (usize, usize))> = self.adjacents(x, y)
             .into_iter()
-            .filter(|&(_, (nx, ny))| self.is_open(nx, ny))
-            .collect()
+            .filter(|&(_, (nx, ny))| is_open(nx, ny))
+            .collect();
+        if let Some(corners) = self.diagonals {
+            list.extend(self.diagonal_adjacents(x, y, corners, &is_open));
+        }
+        if let Some(&(to, _)) = self.portals.get(&(x, y)) {
+            list.push(('*', to));
+        }
+        list
     }
 
-    fn distance(&self, &(x1, y1): &(usize, usize), &(x2, y2): &(usize, usize)) -> usize {
-        fn dist(a: usize, b: usize) -> usize {
-            if a < b { b - a } else { a - b }
+    fn distance(&self, a: &(usize, usize), b: &(usize, usize)) -> usize {
+        if self.portals.get(a).map_or(false, |&(to, _)| to == *b) {
+            return TELEPORT;
         }
-        let d = dist(x1, x2) + dist(y1, y2);
-        if d < 2 { d * 1000 } else { d * 1001 }
+        let direct = self.estimate(a, b);
+        if self.portals.is_empty() || self.offsets(a).contains(b) {
+            return direct;
+        }
+        // any route through portals at least walks to one and out of another
+        let to_portal = self.portals.keys().map(|p| self.estimate(a, p)).min().unwrap();
+        let from_portal = self.portals.keys().map(|p| self.estimate(p, b)).min().unwrap();
+        min(direct, to_portal + TELEPORT + from_portal)
+    }
+
+    fn dense_size(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn dense_index(&self, &(x, y): &(usize, usize)) -> Option<usize> {
+        Some(y * self.width + x)
     }
 }
 
+fn dist(a: usize, b: usize) -> usize {
+    if a < b { b - a } else { a - b }
+}
+
 impl Maze {
     pub fn new(width: usize, height: usize) -> Maze {
         assert!(width > 0 && height > 0,
@@ -87,48 +156,283 @@ impl Maze {
         Maze {
             width: width,
             height: height,
-            open: vec![false; width * height],
-            marked: vec![false; width * height],
+            open: BitGrid::new(width, height),
+            marked: BitGrid::new(width, height),
+            terrain: vec![],
+            min_cost: TERRAIN[0].1,
+            diagonals: None,
+            portals: HashMap::new(),
+            jumped: HashSet::new(),
+            wrap: false,
             start: (1, 1),
             goal: (width - 2, height - 2),
         }
     }
 
+    /// Loads the plain maze format, handing any character it doesn't know to
+    /// `extra` along with its position. `extra` says whether the cell is open,
+    /// or `None` if it doesn't know the character either.
+    ///
+    /// An optional first line starting with `!` lists options for the maze;
+    /// `!wrap` makes it toroidal.
+    ///
+    /// Without an `@` the start is (1, 1), and without an `X` the goal is the
+    /// cell diagonally in from the bottom right corner, as in a generated
+    /// maze. It's an error for either to be a wall.
+    pub fn load_with<R, F>(reader: &mut R, extra: F) -> Result<Maze, LoadError>
+        where R: BufRead,
+              F: FnMut(char, usize, usize) -> Option<bool>
+    {
+        let maze = try!(Maze::load_map_with(reader, extra));
+        if !maze.is_open(maze.start.0, maze.start.1) {
+            return Err(LoadError::MissingStart);
+        }
+        if !maze.is_open(maze.goal.0, maze.goal.1) {
+            return Err(LoadError::MissingExit);
+        }
+        Ok(maze)
+    }
+
+    /// Like `load_with`, for puzzles that don't use the maze's own start and
+    /// goal, so they may be anywhere.
+    pub fn load_map_with<R, F>(reader: &mut R, mut extra: F) -> Result<Maze, LoadError>
+        where R: BufRead,
+              F: FnMut(char, usize, usize) -> Option<bool>
+    {
+        let mut lines: Vec<String> = try!(reader.lines().collect());
+        let mut wrap = false;
+        // lines above the map, to report positions in the file
+        let mut skipped = 0;
+        if lines.first().map_or(false, |l| l.starts_with('!')) {
+            for option in lines.remove(0)[1..].split_whitespace() {
+                match option {
+                    "wrap" => wrap = true,
+                    _ => return Err(LoadError::Invalid(format!("unknown option '{}'", option))),
+                }
+            }
+            skipped = 1;
+        }
+
+        let w = match lines.iter().map(|ref l| l.len()).max() {
+            Some(w) if w > 0 => w,
+            _ => return Err(LoadError::Invalid("empty map".to_string())),
+        };
+        let h = lines.len();
+        if w < 2 || h < 2 {
+            return Err(LoadError::Invalid(format!("map is {}x{}, expected at least 2x2", w, h)));
+        }
+
+        let mut maze = Maze::new(w, h);
+        maze.wrap = wrap;
+        let mut unpaired = HashMap::new();
+        let mut paired = HashSet::new();
+        for (line, y) in lines.iter().zip(0..h) {
+            for (ch, x) in line.chars().zip(0..w) {
+                let open = match ch {
+                    ' ' => true,
+                    '#' => false,
+                    '@' => {
+                        maze.start = (x, y);
+                        true
+                    }
+                    'X' => {
+                        maze.goal = (x, y);
+                        true
+                    }
+                    '0'...'9' => {
+                        if paired.contains(&ch) {
+                            return Err(LoadError::Invalid(format!("{}:{}: portal '{}' appears \
+                                                                   more than twice",
+                                                                  y + skipped + 1,
+                                                                  x + 1,
+                                                                  ch)));
+                        }
+                        if let Some(other) = unpaired.remove(&ch) {
+                            maze.add_portal(other, (x, y), ch);
+                            paired.insert(ch);
+                        } else {
+                            unpaired.insert(ch, (x, y));
+                        }
+                        true
+                    }
+                    _ => {
+                        if let Some(t) = TERRAIN.iter().position(|&(c, _)| c == ch) {
+                            maze.set_terrain(x, y, t);
+                            true
+                        } else {
+                            match extra(ch, x, y) {
+                                Some(open) => open,
+                                None => {
+                                    return Err(LoadError::UnknownChar {
+                                        ch: ch,
+                                        line: y + skipped + 1,
+                                        col: x + 1,
+                                    })
+                                }
+                            }
+                        }
+                    }
+                };
+                if open {
+                    maze.set_open(x, y);
+                }
+            }
+        }
+        if let Some((ch, _)) = unpaired.into_iter().next() {
+            return Err(LoadError::Invalid(format!("portal '{}' has no partner", ch)));
+        }
+        Ok(maze)
+    }
+
+    /// Cost of walking from (x1, y1) to (x2, y2) ignoring portals: exact for
+    /// neighbouring cells, a lower bound otherwise.
+    fn estimate(&self, &(x1, y1): &(usize, usize), &(x2, y2): &(usize, usize)) -> usize {
+        let (mut dx, mut dy) = (dist(x1, x2), dist(y1, y2));
+        if self.wrap {
+            dx = min(dx, self.width - dx);
+            dy = min(dy, self.height - dy);
+        }
+        // octile distance when diagonals are allowed, manhattan otherwise
+        let (straight, diagonal) = if self.diagonals.is_some() {
+            (max(dx, dy) - min(dx, dy), min(dx, dy))
+        } else {
+            (dx + dy, 0)
+        };
+        if straight + diagonal < 2 {
+            let cost = self.step_cost(x2, y2);
+            straight * cost + diagonal * cost * DIAGONAL / 1000
+        } else {
+            straight * (self.min_cost + 1) + diagonal * (self.min_cost * DIAGONAL / 1000 + 1)
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     fn in_bounds(&self, x: usize, y: usize) -> bool {
         x < self.width && y < self.height
     }
 
-    fn is_open(&self, x: usize, y: usize) -> bool {
-        !self.in_bounds(x, y) || self.open[y * self.width + x]
+    pub fn is_open(&self, x: usize, y: usize) -> bool {
+        !self.in_bounds(x, y) || self.open.get(x, y)
+    }
+
+    /// The cell (dx, dy) away from (x, y), wrapping around the edges of a
+    /// toroidal maze and `None` past the edges of any other.
+    fn offset(&self, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+        let (nx, ny) = (x as isize + dx, y as isize + dy);
+        let (w, h) = (self.width as isize, self.height as isize);
+        if self.wrap {
+            Some((((nx % w + w) % w) as usize, ((ny % h + h) % h) as usize))
+        } else if nx < 0 || ny < 0 || nx >= w || ny >= h {
+            None
+        } else {
+            Some((nx as usize, ny as usize))
+        }
+    }
+
+    /// Every cell within one step of `a`, in either direction.
+    fn offsets(&self, &(x, y): &(usize, usize)) -> Vec<(usize, usize)> {
+        let mut list = vec![];
+        for dy in -1..2 {
+            for dx in -1..2 {
+                list.extend(self.offset(x, y, dx, dy));
+            }
+        }
+        list
     }
 
     fn adjacents(&self, x: usize, y: usize) -> Vec<(char, (usize, usize))> {
+        [('<', -1, 0), ('^', 0, -1), ('>', 1, 0), ('v', 0, 1)]
+            .iter()
+            .filter_map(|&(dir, dx, dy)| self.offset(x, y, dx, dy).map(|n| (dir, n)))
+            .collect()
+    }
+
+    fn diagonal_adjacents<F>(&self,
+                             x: usize,
+                             y: usize,
+                             corners: CornerCutting,
+                             is_open: &F)
+                             -> Vec<(char, (usize, usize))>
+        where F: Fn(usize, usize) -> bool
+    {
         let mut list = Vec::<(char, (usize, usize))>::with_capacity(4);
-        // avoid pillars and borders
-        if x > 0 {
-            list.push(('<', (x - 1, y)));
-        }
-        if y > 0 {
-            list.push(('^', (x, y - 1)));
+        for &(dir, dx, dy) in &[('↖', -1, -1), ('↗', 1, -1), ('↘', 1, 1), ('↙', -1, 1)] {
+            let (nx, ny) = match self.offset(x, y, dx, dy) {
+                Some(n) => n,
+                None => continue,
+            };
+            if !is_open(nx, ny) {
+                continue;
+            }
+            let (a, b) = (is_open(nx, y), is_open(x, ny));
+            let allowed = match corners {
+                CornerCutting::Never => a && b,
+                CornerCutting::OneWall => a || b,
+                CornerCutting::Always => true,
+            };
+            if allowed {
+                list.push((dir, (nx, ny)));
+            }
         }
-        if x < self.width - 1 {
-            list.push(('>', (x + 1, y)));
+        list
+    }
+
+    /// Joins two cells so that standing on either lets you jump to the other.
+    pub fn add_portal(&mut self, a: (usize, usize), b: (usize, usize), label: char) {
+        if self.portals.contains_key(&a) || self.portals.contains_key(&b) {
+            panic!("Portal '{}' joins a cell that already has a portal", label);
         }
-        if y < self.height - 1 {
-            list.push(('v', (x, y + 1)));
+        self.portals.insert(a, (b, label));
+        self.portals.insert(b, (a, label));
+    }
+
+    /// Makes the maze toroidal, so moving off one edge enters the opposite one.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn set_start(&mut self, x: usize, y: usize) {
+        self.start = (x, y);
+    }
+
+    pub fn set_goal(&mut self, x: usize, y: usize) {
+        self.goal = (x, y);
+    }
+
+    /// Enables eight-connected movement with the given corner rule, or
+    /// restores plain four-connected movement with `None`.
+    pub fn set_diagonals(&mut self, corners: Option<CornerCutting>) {
+        self.diagonals = corners;
+    }
+
+    pub fn set_open(&mut self, x: usize, y: usize) {
+        if self.in_bounds(x, y) {
+            self.open.set(x, y);
+        } else {
+            panic!("Out of bounds: ({}, {})", x, y);
         }
-        list
     }
 
-    fn set_open(&mut self, x: usize, y: usize) {
+    pub fn set_closed(&mut self, x: usize, y: usize) {
         if self.in_bounds(x, y) {
-            self.open[x + y * self.width] = true;
+            self.open.clear(x, y);
         } else {
             panic!("Out of bounds: ({}, {})", x, y);
         }
     }
 
-    pub fn random(width: usize, height: usize) -> Maze {
+    pub fn random<R: Rng>(width: usize, height: usize, rng: &mut R) -> Maze {
         assert!(width > 0 && height > 0 && width % 2 != 0 && height % 2 != 0,
                 format!("mis-sized maze {}x{}", width, height));
         let mut maze = Self::new(width, height);
@@ -146,7 +450,7 @@ impl Maze {
             }
             maze.set_open(x, y);
             let mut nexts = maze.adjacents(x, y);
-            rand::thread_rng().shuffle(&mut nexts);
+            rng.shuffle(&mut nexts);
             for (_, (nx, ny)) in nexts {
                 stack.push((nx, ny, x, y));
             }
@@ -154,11 +458,117 @@ impl Maze {
         maze
     }
 
+    pub fn set_terrain(&mut self, x: usize, y: usize, terrain: usize) {
+        if !self.in_bounds(x, y) {
+            panic!("Out of bounds: ({}, {})", x, y);
+        }
+        if self.terrain.is_empty() {
+            if terrain == 0 {
+                return;
+            }
+            self.terrain = vec![0; self.width * self.height];
+        }
+        self.terrain[x + y * self.width] = terrain as u8;
+        if TERRAIN[terrain].1 < self.min_cost {
+            self.min_cost = TERRAIN[terrain].1;
+        }
+    }
+
+    /// Cost of stepping onto (x, y), in thousandths of a plain step.
+    pub fn step_cost(&self, x: usize, y: usize) -> usize {
+        TERRAIN[self.terrain_at(x, y)].1
+    }
+
+    fn terrain_at(&self, x: usize, y: usize) -> usize {
+        if self.terrain.is_empty() || !self.in_bounds(x, y) {
+            0
+        } else {
+            self.terrain[x + y * self.width] as usize
+        }
+    }
+
+    fn terrain_char(&self, x: usize, y: usize) -> char {
+        TERRAIN[self.terrain_at(x, y)].0
+    }
+
     pub fn mark(&mut self, x: usize, y: usize) {
-        self.marked[y * self.width + x] = true;
+        self.marked.set(x, y);
+    }
+
+    /// Marks every cell of a solution, noting where it jumps through a portal
+    /// so the jump shows up as `*` at both ends.
+    pub fn mark_path(&mut self, path: &[(char, (usize, usize))]) {
+        let mut at = self.start;
+        for &(edge, (x, y)) in path {
+            if edge == '*' {
+                self.jumped.insert(at);
+                self.jumped.insert((x, y));
+            }
+            self.mark(x, y);
+            at = (x, y);
+        }
     }
+
     pub fn is_marked(&self, x: usize, y: usize) -> bool {
-        self.marked[y * self.width + x]
+        self.marked.get(x, y)
+    }
+
+    /// Draws the maze one string per row, letting `overlay` pick what to show
+    /// at each (x, y) given the character that would otherwise be there.
+    pub fn render_with<F, T>(&self, mut overlay: F) -> Vec<String>
+        where F: FnMut(usize, usize, char) -> T,
+              T: fmt::Display
+    {
+        let rendered = format!("{}", self);
+        rendered.lines()
+            .zip(0..self.height)
+            .map(|(line, y)| {
+                line.chars().zip(0..).map(|(ch, x)| overlay(x, y, ch).to_string()).collect()
+            })
+            .collect()
+    }
+
+    /// How many cells are open.
+    pub fn open_count(&self) -> usize {
+        self.open.count()
+    }
+
+    /// Opens the cells from `from` up to but not including `to` on row `y`.
+    pub fn open_run(&mut self, y: usize, from: usize, to: usize) {
+        if to > self.width || y >= self.height {
+            panic!("Out of bounds: ({}, {})", to - 1, y);
+        }
+        self.open.set_run(y, from, to);
+    }
+
+    fn marked_at(&self, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+        self.offset(x, y, dx, dy).map_or(false, |(nx, ny)| self.is_marked(nx, ny))
+    }
+
+    /// Whether a marked path at (x, y) joins up with the cell (dx, dy) away.
+    /// Paths run on off the edges of a maze that doesn't wrap.
+    fn joined_at(&self, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+        self.offset(x, y, dx, dy).map_or(true, |(nx, ny)| self.is_marked(nx, ny))
+    }
+
+    fn wall_at(&self, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+        self.offset(x, y, dx, dy).map_or(false, |(nx, ny)| !self.is_open(nx, ny))
+    }
+
+    fn marked_orthogonally(&self, x: usize, y: usize) -> bool {
+        self.marked_at(x, y, -1, 0) || self.marked_at(x, y, 1, 0) ||
+        self.marked_at(x, y, 0, -1) || self.marked_at(x, y, 0, 1)
+    }
+
+    fn diagonal_mark(&self, x: usize, y: usize) -> char {
+        let back = self.marked_at(x, y, -1, -1) || self.marked_at(x, y, 1, 1);
+        let forward = self.marked_at(x, y, 1, -1) || self.marked_at(x, y, -1, 1);
+        match (back, forward) {
+            (true, true) => '╳',
+            (true, false) => '╲',
+            (false, true) => '╱',
+            (false, false) => '◆',
+        }
     }
 }
 
@@ -214,20 +624,27 @@ impl fmt::Display for Maze {
             for x in 0..self.width {
                 try!(write!(f,
                             "{}",
-                            if self.is_marked(x, y) {
+                            if self.jumped.contains(&(x, y)) {
+                                '*'
+                            } else if let Some(&(_, label)) = self.portals.get(&(x, y)) {
+                                label
+                            } else if self.is_marked(x, y) && self.diagonals.is_some() &&
+                               !self.marked_orthogonally(x, y) {
+                                self.diagonal_mark(x, y)
+                            } else if self.is_marked(x, y) {
                                 wall(&double,
-                                     x <= 0 || self.is_marked(x - 1, y),
-                                     self.is_marked(x + 1, y),
-                                     y <= 0 || self.is_marked(x, y - 1),
-                                     self.is_marked(x, y + 1))
+                                     self.joined_at(x, y, -1, 0),
+                                     self.joined_at(x, y, 1, 0),
+                                     self.joined_at(x, y, 0, -1),
+                                     self.joined_at(x, y, 0, 1))
                             } else if self.is_open(x, y) {
-                                ' '
+                                self.terrain_char(x, y)
                             } else {
                                 wall(&single,
-                                     x > 0 && !self.is_open(x - 1, y),
-                                     !self.is_open(x + 1, y),
-                                     y > 0 && !self.is_open(x, y - 1),
-                                     !self.is_open(x, y + 1))
+                                     self.wall_at(x, y, -1, 0),
+                                     self.wall_at(x, y, 1, 0),
+                                     self.wall_at(x, y, 0, -1),
+                                     self.wall_at(x, y, 0, 1))
                             }));
             }
             try!(f.write_str("\n"));
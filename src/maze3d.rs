@@ -0,0 +1,216 @@
+use maze::Maze;
+use rand::Rng;
+use search::Graph;
+use std::fmt;
+use std::io::{BufRead, Cursor};
+use stored::{LoadError, Stored};
+
+/// Several `Maze` floors stacked on top of each other and joined by stairs.
+///
+/// The text format is the plain maze format for each floor, bottom first,
+/// with floors separated by a line starting with `-`. A `U` is a stair up to
+/// the same spot on the floor above and a `D` is a stair down; either end of
+/// a staircase is enough to connect the two floors.
+pub struct Maze3d {
+    floors: Vec<Maze>,
+    // stairs[z] holds the cells joining floor z to floor z + 1
+    stairs: Vec<Vec<bool>>,
+    start: (usize, usize, usize),
+    goal: (usize, usize, usize),
+}
+
+impl Maze3d {
+    fn width(&self) -> usize {
+        self.floors[0].width()
+    }
+
+    fn has_stairs(&self, x: usize, y: usize, z: usize) -> bool {
+        z + 1 < self.floors.len() && self.stairs[z][y * self.width() + x]
+    }
+
+    /// Stacks floors of the same size, joining each pair with one staircase
+    /// on a random cell that is open on both.
+    pub fn stack<R: Rng>(floors: Vec<Maze>, rng: &mut R) -> Maze3d {
+        assert!(!floors.is_empty(), "no floors to stack");
+        let (w, h) = (floors[0].width(), floors[0].height());
+        assert!(floors.iter().all(|f| f.width() == w && f.height() == h),
+                "floors must all be the same size");
+        let mut stairs = vec![vec![false; w * h]; floors.len() - 1];
+        for z in 0..stairs.len() {
+            let candidates: Vec<(usize, usize)> = (0..h)
+                .flat_map(|y| (0..w).map(move |x| (x, y)))
+                .filter(|&(x, y)| floors[z].is_open(x, y) && floors[z + 1].is_open(x, y))
+                .filter(|&(x, y)| x > 0 && y > 0 && x < w - 1 && y < h - 1)
+                .collect();
+            if let Some(&(x, y)) = rng.choose(&candidates) {
+                stairs[z][y * w + x] = true;
+            }
+        }
+        let (sx, sy) = floors[0].start();
+        let (gx, gy) = floors[floors.len() - 1].goal();
+        Maze3d {
+            start: (sx, sy, 0),
+            goal: (gx, gy, floors.len() - 1),
+            floors: floors,
+            stairs: stairs,
+        }
+    }
+
+    pub fn mark(&mut self, x: usize, y: usize, z: usize) {
+        self.floors[z].mark(x, y);
+    }
+}
+
+impl Stored for Maze3d {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let lines: Vec<String> = try!(reader.lines().collect());
+        let layers: Vec<&[String]> = lines.split(|l| l.starts_with('-')).collect();
+        let w = lines.iter().filter(|l| !l.starts_with('-')).map(|l| l.len()).max().unwrap_or(0);
+        if w == 0 {
+            return Err(LoadError::Invalid("empty map".to_string()));
+        }
+        // line of the file each floor starts on, less one
+        let mut skipped = 0;
+        let mut floors = vec![];
+        let mut ups = vec![];
+        let mut downs = vec![];
+        let mut start = None;
+        let mut goal = None;
+        for (layer, z) in layers.iter().zip(0..) {
+            let mut up = vec![false; w * layer.len()];
+            let mut down = vec![false; w * layer.len()];
+            let mut text = String::new();
+            for (line, y) in layer.iter().zip(0..) {
+                for (ch, x) in line.chars().zip(0..) {
+                    text.push(match ch {
+                        'U' => {
+                            up[y * w + x] = true;
+                            ' '
+                        }
+                        'D' => {
+                            down[y * w + x] = true;
+                            ' '
+                        }
+                        '@' => {
+                            start = Some((x, y, z));
+                            ch
+                        }
+                        'X' => {
+                            goal = Some((x, y, z));
+                            ch
+                        }
+                        _ => ch,
+                    });
+                }
+                // pad so every floor loads at the same width
+                for _ in line.chars().count()..w {
+                    text.push('#');
+                }
+                text.push('\n');
+            }
+            // the start and goal are checked below, on their own floors
+            let floor = Maze::load_map_with(&mut Cursor::new(text), |_, _, _| None);
+            floors.push(try!(floor.map_err(|e| e.shifted(skipped))));
+            skipped += layer.len() + 1;
+            ups.push(up);
+            downs.push(down);
+        }
+        let h = floors[0].height();
+        if floors.iter().any(|f| f.height() != h) {
+            return Err(LoadError::Invalid("floors must all be the same size".to_string()));
+        }
+        let stairs = (0..floors.len() - 1)
+            .map(|z| (0..w * h).map(|i| ups[z][i] || downs[z + 1][i]).collect())
+            .collect();
+        let last = floors.len() - 1;
+        let (sx, sy) = floors[0].start();
+        let (gx, gy) = floors[last].goal();
+        let start = start.unwrap_or((sx, sy, 0));
+        let goal = goal.unwrap_or((gx, gy, last));
+        if !floors[start.2].is_open(start.0, start.1) {
+            return Err(LoadError::MissingStart);
+        }
+        if !floors[goal.2].is_open(goal.0, goal.1) {
+            return Err(LoadError::MissingExit);
+        }
+        Ok(Maze3d {
+            start: start,
+            goal: goal,
+            floors: floors,
+            stairs: stairs,
+        })
+    }
+}
+
+impl Graph for Maze3d {
+    type Node = (usize, usize, usize);
+    type Edge = char;
+
+    fn null_edge() -> char {
+        ' '
+    }
+
+    fn start(&self) -> (usize, usize, usize) {
+        self.start
+    }
+
+    fn goal(&self) -> (usize, usize, usize) {
+        self.goal
+    }
+
+    fn neighbors(&self, &(x, y, z): &(usize, usize, usize)) -> Vec<(char, (usize, usize, usize))> {
+        let mut list: Vec<(char, (usize, usize, usize))> = self.floors[z]
+            .neighbors(&(x, y))
+            .into_iter()
+            .map(|(dir, (nx, ny))| (dir, (nx, ny, z)))
+            .collect();
+        if self.has_stairs(x, y, z) {
+            list.push(('U', (x, y, z + 1)));
+        }
+        if z > 0 && self.has_stairs(x, y, z - 1) {
+            list.push(('D', (x, y, z - 1)));
+        }
+        list
+    }
+
+    fn distance(&self,
+                &(x1, y1, z1): &(usize, usize, usize),
+                &(x2, y2, z2): &(usize, usize, usize))
+                -> usize {
+        let dz = if z1 < z2 { z2 - z1 } else { z1 - z2 };
+        if dz == 0 {
+            self.floors[z1].distance(&(x1, y1), &(x2, y2))
+        } else {
+            // terrain differs between floors, so take the most optimistic one
+            let flat = self.floors
+                .iter()
+                .map(|f| f.distance(&(x1, y1), &(x2, y2)))
+                .min()
+                .unwrap();
+            flat + dz * 1000
+        }
+    }
+}
+
+impl fmt::Display for Maze3d {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (z, floor) in self.floors.iter().enumerate().rev() {
+            try!(writeln!(f, "Floor {}:", z));
+            let rows = floor.render_with(|x, y, ch| {
+                let up = self.has_stairs(x, y, z);
+                let down = z > 0 && self.has_stairs(x, y, z - 1);
+                match (up, down) {
+                    (true, true) => '↕',
+                    (true, false) => 'U',
+                    (false, true) => 'D',
+                    _ => ch,
+                }
+            });
+            for row in rows {
+                try!(writeln!(f, "{}", row));
+            }
+            try!(f.write_str("\n"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use maze::Maze;
+use search::Graph;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Values closer than this between sweeps count as converged.
+const EPSILON: f64 = 1e-6;
+
+#[derive(Clone, Copy)]
+pub enum Method {
+    ValueIteration,
+    PolicyIteration,
+}
+
+/// A maze on a slippery floor: a move goes the intended way with
+/// probability `1 - slip` and otherwise slides off to either side, half the
+/// time each. Sliding into a wall leaves you where you were, and still costs
+/// a step.
+pub struct Mdp<'a> {
+    maze: &'a Maze,
+    slip: f64,
+    // cells that can reach the goal, with their moves
+    states: Vec<(usize, usize)>,
+    moves: HashMap<(usize, usize), Vec<(char, (usize, usize))>>,
+}
+
+/// A policy along with its expected cost to the goal from each cell, in steps.
+pub struct Plan<'a> {
+    maze: &'a Maze,
+    pub action: HashMap<(usize, usize), char>,
+    pub cost: HashMap<(usize, usize), f64>,
+    pub iterations: usize,
+}
+
+fn sideways(dir: char) -> [char; 2] {
+    match dir {
+        '<' | '>' => ['^', 'v'],
+        _ => ['<', '>'],
+    }
+}
+
+fn arrow(dir: char) -> char {
+    match dir {
+        '<' => '←',
+        '^' => '↑',
+        '>' => '→',
+        _ => '↓',
+    }
+}
+
+impl<'a> Mdp<'a> {
+    pub fn new(maze: &'a Maze, slip: f64) -> Mdp<'a> {
+        if slip < 0.0 || slip >= 1.0 {
+            panic!("Slip probability must be in [0, 1): {}", slip);
+        }
+        let mut moves = HashMap::new();
+        let mut frontier = VecDeque::new();
+        frontier.push_back(maze.start());
+        while let Some(pos) = frontier.pop_front() {
+            if moves.contains_key(&pos) {
+                continue;
+            }
+            // slips only make sense for the four straight moves
+            let straight: Vec<(char, (usize, usize))> = maze.neighbors(&pos)
+                .into_iter()
+                .filter(|&(dir, _)| "<^>v".contains(dir))
+                .collect();
+            frontier.extend(straight.iter().map(|&(_, next)| next));
+            moves.insert(pos, straight);
+        }
+        let mut mdp = Mdp {
+            maze: maze,
+            slip: slip,
+            states: vec![],
+            moves: moves,
+        };
+        mdp.states = mdp.proper_policy().keys().cloned().collect();
+        mdp.states.sort();
+        mdp
+    }
+
+    /// Where taking `dir` from `pos` can end up, with probability and cost.
+    fn outcomes(&self, pos: (usize, usize), dir: char) -> Vec<(f64, (usize, usize), f64)> {
+        let moves = &self.moves[&pos];
+        let land = |d: char| {
+            let to = moves.iter().find(|&&(m, _)| m == d).map_or(pos, |&(_, to)| to);
+            let cost = if to == pos { 1000 } else { self.maze.distance(&pos, &to) };
+            (to, cost as f64 / 1000.0)
+        };
+        let mut list = vec![];
+        let (to, cost) = land(dir);
+        list.push((1.0 - self.slip, to, cost));
+        if self.slip > 0.0 {
+            for &d in sideways(dir).iter() {
+                let (to, cost) = land(d);
+                list.push((self.slip / 2.0, to, cost));
+            }
+        }
+        list
+    }
+
+    fn q_value(&self, cost: &HashMap<(usize, usize), f64>, pos: (usize, usize), dir: char) -> f64 {
+        self.outcomes(pos, dir)
+            .into_iter()
+            .map(|(p, to, step)| p * (step + cost.get(&to).cloned().unwrap_or(0.0)))
+            .sum()
+    }
+
+    fn best_action(&self, cost: &HashMap<(usize, usize), f64>, pos: (usize, usize)) -> (char, f64) {
+        self.moves[&pos]
+            .iter()
+            .map(|&(dir, _)| (dir, self.q_value(cost, pos, dir)))
+            .fold(None, |best: Option<(char, f64)>, (dir, q)| {
+                match best {
+                    Some((_, b)) if b <= q => best,
+                    _ => Some((dir, q)),
+                }
+            })
+            .unwrap()
+    }
+
+    /// Walks back from the goal so every cell that can reach it gets a move
+    /// towards it. This always reaches the goal eventually, which is what
+    /// policy iteration needs to start from.
+    fn proper_policy(&self) -> HashMap<(usize, usize), char> {
+        let goal = self.maze.goal();
+        let mut into: HashMap<(usize, usize), Vec<((usize, usize), char)>> = HashMap::new();
+        for (&from, moves) in &self.moves {
+            for &(dir, to) in moves {
+                into.entry(to).or_insert_with(Vec::new).push((from, dir));
+            }
+        }
+        let mut policy = HashMap::new();
+        let mut frontier = VecDeque::new();
+        frontier.push_back(goal);
+        while let Some(pos) = frontier.pop_front() {
+            for &(from, dir) in into.get(&pos).map_or(&[][..], |v| &v[..]) {
+                if from == goal || policy.contains_key(&from) {
+                    continue;
+                }
+                policy.insert(from, dir);
+                frontier.push_back(from);
+            }
+        }
+        policy
+    }
+
+    fn plan(&self,
+            action: HashMap<(usize, usize), char>,
+            cost: HashMap<(usize, usize), f64>,
+            iterations: usize)
+            -> Plan<'a> {
+        Plan {
+            maze: self.maze,
+            action: action,
+            cost: cost,
+            iterations: iterations,
+        }
+    }
+
+    pub fn solve(&self, method: Method) -> Plan<'a> {
+        match method {
+            Method::ValueIteration => self.value_iteration(),
+            Method::PolicyIteration => self.policy_iteration(),
+        }
+    }
+
+    pub fn value_iteration(&self) -> Plan<'a> {
+        let mut cost: HashMap<(usize, usize), f64> =
+            self.states.iter().map(|&s| (s, 0.0)).collect();
+        let mut iterations = 0;
+        loop {
+            iterations += 1;
+            let mut change: f64 = 0.0;
+            for &s in &self.states {
+                let (_, q) = self.best_action(&cost, s);
+                change = change.max((q - cost[&s]).abs());
+                cost.insert(s, q);
+            }
+            if change < EPSILON {
+                break;
+            }
+        }
+        let action = self.states.iter().map(|&s| (s, self.best_action(&cost, s).0)).collect();
+        self.plan(action, cost, iterations)
+    }
+
+    pub fn policy_iteration(&self) -> Plan<'a> {
+        let mut policy = self.proper_policy();
+        let mut cost: HashMap<(usize, usize), f64> =
+            self.states.iter().map(|&s| (s, 0.0)).collect();
+        let mut iterations = 0;
+        loop {
+            iterations += 1;
+            // evaluate the current policy
+            loop {
+                let mut change: f64 = 0.0;
+                for &s in &self.states {
+                    let q = self.q_value(&cost, s, policy[&s]);
+                    change = change.max((q - cost[&s]).abs());
+                    cost.insert(s, q);
+                }
+                if change < EPSILON {
+                    break;
+                }
+            }
+            // then improve it, only switching for a real gain
+            let mut stable = true;
+            for &s in &self.states {
+                let (dir, q) = self.best_action(&cost, s);
+                if q < self.q_value(&cost, s, policy[&s]) - EPSILON {
+                    policy.insert(s, dir);
+                    stable = false;
+                }
+            }
+            if stable {
+                break;
+            }
+        }
+        self.plan(policy, cost, iterations)
+    }
+}
+
+impl<'a> fmt::Display for Plan<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self.maze.render_with(|x, y, ch| {
+            self.action.get(&(x, y)).map_or(ch, |&dir| arrow(dir))
+        });
+        for row in rows {
+            try!(writeln!(f, "{}", row));
+        }
+        try!(writeln!(f, "\nExpected cost to goal:"));
+        for y in 0..self.maze.height() {
+            let mut row = String::new();
+            for x in 0..self.maze.width() {
+                row.push_str(&if (x, y) == self.maze.goal() {
+                    format!("{:>6}", "X")
+                } else if let Some(cost) = self.cost.get(&(x, y)) {
+                    format!("{:>6.1}", cost)
+                } else if self.maze.is_open(x, y) {
+                    format!("{:>6}", "-")
+                } else {
+                    format!("{:>6}", "#")
+                });
+            }
+            try!(writeln!(f, "{}", row.trim_right()));
+        }
+        Ok(())
+    }
+}
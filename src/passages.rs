@@ -0,0 +1,64 @@
+use rand::Rng;
+use stored::LoadError;
+
+/// A grid of cells joined by passages in a fixed set of directions, like the
+/// hex and triangle mazes. Directions are numbered from 0.
+pub trait Passages {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// The cells next to (c, r), with the direction to each.
+    fn adjacents(&self, c: usize, r: usize) -> Vec<(usize, (usize, usize))>;
+
+    /// Opens the passage in direction `d` between (c, r) and its neighbour
+    /// (nc, nr), from both sides.
+    fn join(&mut self, c: usize, r: usize, d: usize, nc: usize, nr: usize);
+
+    fn set_passage(&mut self, c: usize, r: usize, d: usize) {
+        if let Some(&(_, (nc, nr))) = self.adjacents(c, r).iter().find(|&&(nd, _)| nd == d) {
+            self.join(c, r, d, nc, nr);
+        } else {
+            panic!("No neighbour {} of ({}, {})", d, c, r);
+        }
+    }
+
+    /// Opens the passage drawn at column `x` of line `y` of a map, or says
+    /// why it can't be there.
+    fn load_passage(&mut self,
+                    c: usize,
+                    r: usize,
+                    d: usize,
+                    x: usize,
+                    y: usize)
+                    -> Result<(), LoadError> {
+        if !self.adjacents(c, r).iter().any(|&(nd, _)| nd == d) {
+            return Err(LoadError::Invalid(format!("{}:{}: passage leads off the map",
+                                                  y + 1,
+                                                  x + 1)));
+        }
+        self.set_passage(c, r, d);
+        Ok(())
+    }
+}
+
+/// Carves a randomized depth-first backtracker maze into `maze`, which
+/// should have every passage closed.
+pub fn backtrack<P: Passages, R: Rng>(maze: &mut P, rng: &mut R) {
+    let width = maze.width();
+    let mut visited = vec![false; width * maze.height()];
+    visited[0] = true;
+    let mut stack = vec![(0, 0)];
+    while let Some(&(c, r)) = stack.last() {
+        let nexts: Vec<(usize, (usize, usize))> = maze.adjacents(c, r)
+            .into_iter()
+            .filter(|&(_, (nc, nr))| !visited[nr * width + nc])
+            .collect();
+        if let Some(&(d, (nc, nr))) = rng.choose(&nexts) {
+            visited[nr * width + nc] = true;
+            maze.set_passage(c, r, d);
+            stack.push((nc, nr));
+        } else {
+            stack.pop();
+        }
+    }
+}
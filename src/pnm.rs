@@ -0,0 +1,172 @@
+use maze::Maze;
+use search::Graph;
+use std::io::{self, BufRead, Write};
+use std::str::SplitWhitespace;
+use stored::{LoadError, Stored};
+
+const WALL: [u8; 3] = [0, 0, 0];
+const OPEN: [u8; 3] = [255, 255, 255];
+const PATH: [u8; 3] = [220, 40, 40];
+const START: [u8; 3] = [40, 180, 40];
+const GOAL: [u8; 3] = [40, 80, 220];
+
+/// A maze read from a plain PBM (`P1`) or PGM (`P2`) image, one pixel per
+/// cell.
+///
+/// In a PBM, black pixels are walls. In a PGM, pixels darker than a quarter
+/// of the maximum are walls and everything else is open, except that the
+/// first pixel from the darker half of the middle greys marks the start and
+/// the first from the lighter half marks the goal. Without those, the start
+/// and goal are the maze defaults or come from a sidecar (see `load_points`).
+pub struct Pnm(pub Maze);
+
+fn number(tokens: &mut SplitWhitespace, what: &str) -> Result<usize, LoadError> {
+    tokens.next()
+        .and_then(|t| t.parse().ok())
+        .ok_or_else(|| LoadError::Invalid(format!("expected image {}", what)))
+}
+
+impl Stored for Pnm {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let mut text = String::new();
+        for line in reader.lines() {
+            let line = try!(line);
+            // comments run from '#' to the end of the line
+            text.push_str(line.split('#').next().unwrap());
+            text.push('\n');
+        }
+        let mut tokens = text.split_whitespace();
+        let magic = match tokens.next() {
+            Some(magic) => magic,
+            None => return Err(LoadError::Invalid("empty image".to_string())),
+        };
+        let width = try!(number(&mut tokens, "width"));
+        let height = try!(number(&mut tokens, "height"));
+        if width < 2 || height < 2 {
+            return Err(LoadError::Invalid(format!("image is {}x{}, expected at least 2x2",
+                                                  width,
+                                                  height)));
+        }
+        let mut maze = Maze::new(width, height);
+        let pixels: Vec<usize> = match magic {
+            // pbm pixels needn't be separated
+            "P1" => {
+                let rest: String = tokens.collect();
+                rest.chars()
+                    .filter(|&ch| ch == '0' || ch == '1')
+                    .map(|ch| if ch == '1' { 0 } else { 1 })
+                    .collect()
+            }
+            "P2" => {
+                let max = try!(number(&mut tokens, "maximum"));
+                let mut values = vec![];
+                for _ in 0..width * height {
+                    values.push(try!(number(&mut tokens, "pixel")) * 4 / (max + 1));
+                }
+                values
+            }
+            _ => {
+                return Err(LoadError::Invalid(format!("unsupported image type '{}', expected \
+                                                       P1 or P2",
+                                                      magic)))
+            }
+        };
+        if pixels.len() < width * height {
+            return Err(LoadError::Invalid(format!("image has {} pixels, expected {}",
+                                                  pixels.len(),
+                                                  width * height)));
+        }
+        let (mut start, mut goal) = (None, None);
+        for y in 0..height {
+            for x in 0..width {
+                // in quarters of the brightness range, or 0 and 1 for pbm
+                let quarter = if magic == "P1" {
+                    pixels[y * width + x] * 3
+                } else {
+                    pixels[y * width + x]
+                };
+                if quarter == 0 {
+                    continue;
+                }
+                maze.set_open(x, y);
+                if quarter == 1 && start.is_none() {
+                    start = Some((x, y));
+                } else if quarter == 2 && goal.is_none() {
+                    goal = Some((x, y));
+                }
+            }
+        }
+        if let Some((x, y)) = start {
+            maze.set_start(x, y);
+        }
+        if let Some((x, y)) = goal {
+            maze.set_goal(x, y);
+        }
+        Ok(Pnm(maze))
+    }
+}
+
+/// Reads a start and goal sidecar for an image, with lines like `start 1 1`
+/// and `goal 39 39`.
+pub fn load_points<R: BufRead>(maze: &mut Maze, reader: &mut R) -> Result<(), LoadError> {
+    for (line, n) in reader.lines().zip(1..) {
+        let line = try!(line);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let point: Result<Vec<usize>, _> = words[1..].iter().map(|w| w.parse()).collect();
+        let (x, y) = match point {
+            Ok(ref xy) if xy.len() == 2 => (xy[0], xy[1]),
+            _ => {
+                return Err(LoadError::Invalid(format!("{}: expected 'start X Y' or 'goal X Y'",
+                                                      n)))
+            }
+        };
+        let set: fn(&mut Maze, usize, usize) = match words[0] {
+            "start" => Maze::set_start,
+            "goal" => Maze::set_goal,
+            other => return Err(LoadError::Invalid(format!("{}: unexpected point '{}'", n, other))),
+        };
+        if x >= maze.width() || y >= maze.height() || !maze.is_open(x, y) {
+            return Err(LoadError::Invalid(format!("{}: {} ({}, {}) isn't an open pixel",
+                                                  n,
+                                                  words[0],
+                                                  x,
+                                                  y)));
+        }
+        set(maze, x, y);
+    }
+    Ok(())
+}
+
+/// Writes `maze` as a binary PPM with each cell `scale` pixels square, the
+/// marked path in red, the start green and the goal blue.
+pub fn save_ppm<W: Write>(maze: &Maze, scale: usize, writer: &mut W) -> io::Result<()> {
+    let (width, height) = (maze.width(), maze.height());
+    try!(write!(writer, "P6\n{} {}\n255\n", width * scale, height * scale));
+    let mut row = Vec::with_capacity(width * scale * 3);
+    for y in 0..height {
+        row.clear();
+        for x in 0..width {
+            let color = if (x, y) == maze.start() {
+                START
+            } else if (x, y) == maze.goal() {
+                GOAL
+            } else if maze.is_marked(x, y) {
+                PATH
+            } else if maze.is_open(x, y) {
+                OPEN
+            } else {
+                WALL
+            };
+            for _ in 0..scale {
+                row.extend_from_slice(&color);
+            }
+        }
+        for _ in 0..scale {
+            try!(writer.write_all(&row));
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,5 @@
+use bits::BitSet;
 use std::collections::{HashMap, HashSet};
-use std::collections::hash_map::Entry::{Vacant, Occupied};
 use std::collections::binary_heap::BinaryHeap;
 use std::hash::Hash;
 use std::cmp::{Eq, Ord, Ordering};
@@ -14,11 +14,64 @@ pub trait Graph {
     fn goal(&self) -> Self::Node;
     fn neighbors(&self, n: &Self::Node) -> Vec<(Self::Edge, Self::Node)>;
     fn distance(&self, n1: &Self::Node, n2: &Self::Node) -> usize;
+
+    /// How many nodes `dense_index` numbers.
+    fn dense_size(&self) -> usize {
+        0
+    }
+
+    /// A number below `dense_size` for `n`, if the graph numbers its nodes,
+    /// so that searches can keep what they know about it in bitmaps instead
+    /// of hash sets. Nodes without one are kept in hash sets as usual.
+    fn dense_index(&self, _n: &Self::Node) -> Option<usize> {
+        None
+    }
+}
+
+/// The nodes a search has been through.
+struct Visited<'a, G: Graph + 'a> {
+    graph: &'a G,
+    dense: BitSet,
+    sparse: HashSet<G::Node>,
+}
+
+impl<'a, G: Graph> Visited<'a, G> {
+    fn new(graph: &'a G) -> Visited<'a, G> {
+        Visited {
+            graph: graph,
+            dense: BitSet::new(graph.dense_size()),
+            sparse: HashSet::new(),
+        }
+    }
+
+    fn contains(&self, n: &G::Node) -> bool {
+        match self.graph.dense_index(n) {
+            Some(i) => self.dense.contains(i),
+            None => self.sparse.contains(n),
+        }
+    }
+
+    /// Adds `n`, returning whether it was new.
+    fn insert(&mut self, n: &G::Node) -> bool {
+        match self.graph.dense_index(n) {
+            Some(i) => self.dense.insert(i),
+            None => self.sparse.insert(n.clone()),
+        }
+    }
+
+    fn remove(&mut self, n: &G::Node) {
+        match self.graph.dense_index(n) {
+            Some(i) => self.dense.remove(i),
+            None => {
+                self.sparse.remove(n);
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
 /// Wrapper for holding objects in a priority queue, ordered by S
-struct QueueEntry<S: PartialOrd, T>(S, T);
+pub struct QueueEntry<S: PartialOrd, T>(pub S, pub T);
 
 impl<S: PartialOrd, T> Eq for QueueEntry<S, T> {}
 impl<S: PartialOrd, T> PartialEq for QueueEntry<S, T> {
@@ -41,119 +94,249 @@ impl<S: PartialOrd, T> Ord for QueueEntry<S, T> {
 }
 
 pub fn dfs_search<G: Graph>(graph: &G) -> Option<Vec<(G::Edge, G::Node)>> {
-    let mut visited = HashSet::new();
+    let (result, _, seen) = dfs(graph, false);
+    println!("States visited: {}", seen);
+    result
+}
+
+/// Depth-first search that returns the states it expanded, in order, instead
+/// of printing how many there were.
+pub fn dfs_traced<G: Graph>(graph: &G) -> (Option<Vec<(G::Edge, G::Node)>>, Vec<G::Node>) {
+    let (result, expanded, _) = dfs(graph, true);
+    (result, expanded)
+}
+
+/// Depth-first search that returns how many states it expanded instead of
+/// printing it.
+pub fn dfs_counted<G: Graph>(graph: &G) -> (Option<Vec<(G::Edge, G::Node)>>, usize) {
+    let (result, _, seen) = dfs(graph, false);
+    (result, seen)
+}
+
+/// Depth-first search with an explicit stack, so that long paths through big
+/// mazes can't overflow the call stack. Nodes leave the visited set again
+/// when the search backs out of them. Only keeps the expanded states if
+/// `trace` is set, but always counts them.
+fn dfs<G: Graph>(graph: &G, trace: bool) -> (Option<Vec<(G::Edge, G::Node)>>, Vec<G::Node>, usize) {
     let goal = graph.goal();
-    let mut visits = 0;
-    fn dfs<G: Graph>(visited: &mut HashSet<G::Node>,
-                     visits: &mut usize,
-                     graph: &G,
-                     current: G::Node,
-                     goal: &G::Node)
-                     -> Option<Vec<(G::Edge, G::Node)>> {
-        if current == *goal {
-            return Some(vec![]);
-        }
-        visited.insert(current.clone());
-        *visits += 1;
-        for (edge, neighbor) in graph.neighbors(&current) {
-            if !visited.contains(&neighbor) {
-                if let Some(mut path) = dfs(visited, visits, graph, neighbor.clone(), goal) {
+    let start = graph.start();
+    if start == goal {
+        return (Some(vec![]), vec![], 0);
+    }
+    let mut visited = Visited::new(graph);
+    let mut expanded = vec![];
+    let mut seen = 1;
+    visited.insert(&start);
+    if trace {
+        expanded.push(start.clone());
+    }
+    let mut stack = vec![(G::null_edge(), start.clone(), graph.neighbors(&start).into_iter())];
+    loop {
+        let next = match stack.last_mut() {
+            Some(&mut (_, _, ref mut neighbors)) => neighbors.next(),
+            None => return (None, expanded, seen),
+        };
+        match next {
+            Some((edge, neighbor)) => {
+                if visited.contains(&neighbor) {
+                    continue;
+                }
+                if neighbor == goal {
+                    let mut path: Vec<(G::Edge, G::Node)> = stack.into_iter()
+                        .skip(1)
+                        .map(|(edge, node, _)| (edge, node))
+                        .collect();
                     path.push((edge, neighbor));
-                    return Some(path);
+                    return (Some(path), expanded, seen);
+                }
+                visited.insert(&neighbor);
+                seen += 1;
+                if trace {
+                    expanded.push(neighbor.clone());
+                }
+                let neighbors = graph.neighbors(&neighbor).into_iter();
+                stack.push((edge, neighbor, neighbors));
+            }
+            None => {
+                let (_, node, _) = stack.pop().unwrap();
+                visited.remove(&node);
+            }
+        }
+    }
+}
+
+pub fn a_star_search<G: Graph>(graph: &G) -> Option<Vec<(G::Edge, G::Node)>> {
+    let (result, _, seen) = a_star(graph, false);
+    println!("States visited: {}", seen);
+    result
+}
+
+/// A* search that returns the states it expanded, in order, instead of
+/// printing how many it saw.
+pub fn a_star_traced<G: Graph>(graph: &G) -> (Option<Vec<(G::Edge, G::Node)>>, Vec<G::Node>) {
+    let (result, expanded, _) = a_star(graph, true);
+    (result, expanded)
+}
+
+/// What a search found out about a node it reached.
+struct State<G: Graph> {
+    prior: Option<G::Node>,
+    prior_cost: usize,
+    dir: G::Edge,
+}
+
+/// The nodes a search has reached, each with the cheapest known cost to it
+/// and the step that got there. Only numbered nodes' reached flags are kept
+/// in a bitmap, which saves hashing the many that are never reached; what
+/// was found about each node goes in a hash map, so the table grows with the
+/// search rather than the graph.
+pub struct Table<'a, G: Graph + 'a> {
+    graph: &'a G,
+    reached: BitSet,
+    states: HashMap<G::Node, State<G>>,
+}
+
+impl<'a, G: Graph> Table<'a, G> {
+    fn new(graph: &'a G) -> Table<'a, G> {
+        Table {
+            graph: graph,
+            reached: BitSet::new(graph.dense_size()),
+            states: HashMap::new(),
+        }
+    }
+
+    /// How many nodes have been reached.
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    fn get(&self, n: &G::Node) -> Option<&State<G>> {
+        match self.graph.dense_index(n) {
+            Some(i) if !self.reached.contains(i) => None,
+            _ => self.states.get(n),
+        }
+    }
+
+    /// The cheapest known cost of getting to `n`, if it was reached.
+    pub fn cost(&self, n: &G::Node) -> Option<usize> {
+        self.get(n).map(|state| state.prior_cost)
+    }
+
+    /// The step that reached `n` and where it came from, if `n` was reached.
+    fn step(&self, n: &G::Node) -> Option<(G::Edge, Option<G::Node>)> {
+        self.get(n).map(|state| (state.dir.clone(), state.prior.clone()))
+    }
+
+    /// Records `n` as reached, or a better way of reaching it.
+    fn insert(&mut self, n: G::Node, state: State<G>) {
+        if let Some(i) = self.graph.dense_index(&n) {
+            self.reached.insert(i);
+        }
+        self.states.insert(n, state);
+    }
+
+    /// Walks the recorded steps back from `to`, returning the path from where
+    /// the search started in the same form as the searches, without the
+    /// starting node.
+    pub fn path_to(&self, to: &G::Node) -> Option<Vec<(G::Edge, G::Node)>> {
+        let mut path = vec![];
+        let mut node = to.clone();
+        loop {
+            match self.step(&node) {
+                None => return None,
+                Some((_, None)) => break,
+                Some((edge, Some(prior))) => {
+                    path.push((edge, node));
+                    node = prior;
                 }
             }
         }
-        visited.remove(&current);
-        None
-    };
-    let result = dfs(&mut visited, &mut visits, graph, graph.start(), &goal);
-    println!("States visited: {}", visits);
-    if let Some(mut path) = result {
         path.reverse();
         Some(path)
-    } else {
-        None
     }
 }
 
-pub fn a_star_search<G: Graph>(graph: &G) -> Option<Vec<(G::Edge, G::Node)>> {
-    struct State<G: Graph> {
-        visited: bool,
-        prior: Option<G::Node>,
-        prior_cost: usize,
-        cost_guess: usize,
-        dir: G::Edge,
-    };
-    let mut table = HashMap::new();
+/// Only keeps the expanded states if `trace` is set.
+fn a_star<G: Graph>(graph: &G,
+                    trace: bool)
+                    -> (Option<Vec<(G::Edge, G::Node)>>, Vec<G::Node>, usize) {
+    let mut table = Table::new(graph);
+    let mut visited = Visited::new(graph);
     let start = graph.start();
     let goal = graph.goal();
     let start_cost_guess = graph.distance(&start, &goal);
     table.insert(start.clone(),
                  State::<G> {
-                     visited: false,
                      prior: None,
                      prior_cost: 0,
-                     cost_guess: start_cost_guess,
                      dir: G::null_edge(),
                  });
     let mut frontier = BinaryHeap::new();
-    frontier.push(QueueEntry(start_cost_guess, start));
+    let mut expanded = vec![];
+    frontier.push(QueueEntry(start_cost_guess, start.clone()));
     while let Some(QueueEntry(_, ref current)) = frontier.pop() {
         if current == &goal {
-            let mut path = vec![];
-            let mut node = current;
-            loop {
-                let entry = table.get(node).unwrap();
-                path.push((entry.dir.clone(), node.clone()));
-                if let &Some(ref next) = &entry.prior {
-                    node = next;
-                } else {
-                    break;
-                }
-            }
-            path.reverse();
-            println!("States visited: {}", table.len());
-            return Some(path);
-        }
-        let prior_cost = {
-            let entry = table.get_mut(current).unwrap();
-            if entry.visited {
-                continue;
-            }
-            entry.visited = true;
-            entry.prior_cost
-        };
+            let mut path = vec![(G::null_edge(), start)];
+            path.extend(table.path_to(current).unwrap());
+            return (Some(path), expanded, table.len());
+        }
+        if !visited.insert(current) {
+            continue;
+        }
+        let prior_cost = table.cost(current).unwrap();
+        if trace {
+            expanded.push(current.clone());
+        }
         for (dir, neighbor) in graph.neighbors(current) {
             let new_prior_cost = prior_cost + graph.distance(current, &neighbor);
             let cost_guess = new_prior_cost + graph.distance(&neighbor, &goal);
-            let candidate_entry = State::<G> {
-                visited: false,
-                prior: Some(current.clone()),
-                prior_cost: new_prior_cost,
-                cost_guess: cost_guess,
-                dir: dir,
-            };
-            // if unseen or cost_guess is better, update/insert and requeue
-            let should_enqueue = match table.entry(neighbor.clone()) {
-                Occupied(occ) => {
-                    let v = occ.into_mut();
-                    if v.cost_guess > cost_guess {
-                        *v = candidate_entry;
-                        true
-                    } else {
-                        false
-                    }
-                }
-                Vacant(vac) => {
-                    vac.insert(candidate_entry);
-                    true
-                }
-            };
-            if should_enqueue {
+            // if unseen or cheaper, update/insert and requeue; the guess at the
+            // rest of the way is the same whichever way the neighbour is reached
+            if table.cost(&neighbor).map_or(true, |old| old > new_prior_cost) {
+                table.insert(neighbor.clone(),
+                             State::<G> {
+                                 prior: Some(current.clone()),
+                                 prior_cost: new_prior_cost,
+                                 dir: dir,
+                             });
+                // reopen it, as it may lead somewhere cheaper too
+                visited.remove(&neighbor);
                 frontier.push(QueueEntry(cost_guess, neighbor));
             }
         }
     }
-    println!("States visited: {}", table.len());
-    None
+    (None, expanded, table.len())
+}
+
+/// Uniform-cost search outward from `from` over every reachable node,
+/// recording the cost of each and the path to it.
+pub fn shortest_paths<'a, G: Graph>(graph: &'a G, from: &G::Node) -> Table<'a, G> {
+    let mut table = Table::new(graph);
+    let mut done = Visited::new(graph);
+    table.insert(from.clone(),
+                 State::<G> {
+                     prior: None,
+                     prior_cost: 0,
+                     dir: G::null_edge(),
+                 });
+    let mut frontier = BinaryHeap::new();
+    frontier.push(QueueEntry(0, from.clone()));
+    while let Some(QueueEntry(cost, current)) = frontier.pop() {
+        if !done.insert(&current) {
+            continue;
+        }
+        for (edge, neighbor) in graph.neighbors(&current) {
+            let new_cost = cost + graph.distance(&current, &neighbor);
+            if table.cost(&neighbor).map_or(true, |old| new_cost < old) {
+                table.insert(neighbor.clone(),
+                             State::<G> {
+                                 prior: Some(current.clone()),
+                                 prior_cost: new_cost,
+                                 dir: edge,
+                             });
+                frontier.push(QueueEntry(new_cost, neighbor));
+            }
+        }
+    }
+    table
 }
@@ -1,7 +1,7 @@
 use search::Graph;
 use n_array::NArray;
-use std::io::BufRead;
-use stored::Stored;
+use std::io::{self, BufRead, Write};
+use stored::{LoadError, Stored};
 // use std::fmt;
 
 pub struct Level {
@@ -16,6 +16,9 @@ pub struct Level {
     exit: (u8, u8),
 }
 
+/// Fruits are numbered 10 to 50 in the map.
+const MAX_FRUITS: usize = 41;
+
 enum Cell {
     Block,
     Kill,
@@ -24,8 +27,8 @@ enum Cell {
 }
 
 impl Level {
-    fn from_chars(lines: &Vec<String>) -> Self {
-        let w = lines.iter().map(|ref l| l.len()).max().unwrap();
+    fn from_chars(lines: &Vec<String>) -> Result<Self, LoadError> {
+        let w = lines.iter().map(|ref l| l.len()).max().unwrap_or(0);
         let h = lines.len();
         let mut level = Level {
             map: NArray::new(2, &[w, h]),
@@ -41,6 +44,13 @@ impl Level {
                     '#' => 1,
                     '$' => 2,
                     '@' | 'f' => {
+                        if level.fruits.len() == MAX_FRUITS {
+                            return Err(LoadError::TooManyFruits {
+                                max: MAX_FRUITS,
+                                line: y + 1,
+                                col: x + 1,
+                            });
+                        }
                         let k = 10 + level.fruits.len();
                         level.fruits.push(pos);
                         k as u8
@@ -55,21 +65,38 @@ impl Level {
                         if index >= path.len() {
                             path.resize(index + 1, (0xFF, 0xFF));
                         }
+                        if path[index] != (0xFF, 0xFF) {
+                            return Err(LoadError::DuplicateSegment {
+                                segment: index,
+                                line: y + 1,
+                                col: x + 1,
+                            });
+                        }
                         path[index] = pos;
                         0
                     }
                     _ => {
-                        panic!("Unexpected char: '{}'", ch);
+                        return Err(LoadError::UnknownChar {
+                            ch: ch,
+                            line: y + 1,
+                            col: x + 1,
+                        });
                     }
                 }
             }
         }
+        if level.initial_snake.is_empty() {
+            return Err(LoadError::MissingStart);
+        }
+        if level.exit == (0xFF, 0xFF) {
+            return Err(LoadError::MissingExit);
+        }
         for (&(x, y), i) in level.initial_snake.iter().zip(0..) {
-            if x == 0xCF || y == 0xCF {
-                panic!("No position for position {} in snake!", i);
+            if x == 0xFF || y == 0xFF {
+                return Err(LoadError::MissingSegment(i));
             }
         }
-        level
+        Ok(level)
     }
     fn char(&self, x: usize, y: usize) -> char {
         match self.map[&[x, y]] {
@@ -145,10 +172,29 @@ impl Level {
 }
 
 impl Stored for Level {
-    fn load<R: BufRead>(reader: &mut R) -> Self {
-        let lines: Vec<String> = reader.lines().map(|l| l.ok().unwrap()).collect();
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let lines: Vec<String> = try!(reader.lines().collect());
         Level::from_chars(&lines)
     }
+
+    fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let dim = &self.map.magnitudes;
+        for y in 0..dim[1] {
+            let mut line = String::new();
+            for x in 0..dim[0] {
+                let pos = (x as u8, y as u8);
+                line.push(if pos == self.exit {
+                    'X'
+                } else if let Some(i) = self.initial_snake.iter().position(|&p| p == pos) {
+                    (b'0' + i as u8) as char
+                } else {
+                    self.char(x, y)
+                });
+            }
+            try!(writeln!(writer, "{}", line));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Hash, Eq, PartialEq)]
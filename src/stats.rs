@@ -0,0 +1,148 @@
+use maze::Maze;
+use search::{self, Graph};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Structural measures of a maze, over its open cells and the moves between
+/// them.
+pub struct Stats {
+    pub cells: usize,
+    /// Steps on the shortest solution, if there is one.
+    pub solution_length: Option<usize>,
+    /// Cost of the shortest solution in the same units as `distance`.
+    pub solution_cost: Option<usize>,
+    /// Cells other than the start and goal with only one way in or out.
+    pub dead_ends: usize,
+    /// How many cells have each number of neighbours, indexed by count.
+    pub branching: Vec<usize>,
+    /// Lengths of the runs of cells with exactly two neighbours.
+    pub corridors: Vec<usize>,
+    /// Cells on the solution where there's more than one way on.
+    pub decision_points: usize,
+    /// Independent cycles: how many more moves there are than a tree needs.
+    pub loops: usize,
+}
+
+pub fn analyze(maze: &Maze) -> Stats {
+    let mut open = vec![];
+    for y in 0..maze.height() {
+        for x in 0..maze.width() {
+            if maze.is_open(x, y) {
+                open.push((x, y));
+            }
+        }
+    }
+    let degree = |cell: &(usize, usize)| maze.neighbors(cell).len();
+    let (start, goal) = (maze.start(), maze.goal());
+
+    let mut branching = vec![];
+    let mut dead_ends = 0;
+    let mut edges = 0;
+    for cell in &open {
+        let d = degree(cell);
+        if branching.len() <= d {
+            branching.resize(d + 1, 0);
+        }
+        branching[d] += 1;
+        edges += d;
+        if d == 1 && *cell != start && *cell != goal {
+            dead_ends += 1;
+        }
+    }
+
+    let mut corridors = vec![];
+    let mut seen = HashSet::new();
+    for cell in open.iter().filter(|c| degree(c) == 2) {
+        if !seen.insert(*cell) {
+            continue;
+        }
+        let mut length = 0;
+        let mut stack = vec![*cell];
+        while let Some(at) = stack.pop() {
+            length += 1;
+            for (_, next) in maze.neighbors(&at) {
+                if degree(&next) == 2 && seen.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        corridors.push(length);
+    }
+
+    let mut components = 0;
+    let mut seen = HashSet::new();
+    for cell in &open {
+        if !seen.insert(*cell) {
+            continue;
+        }
+        components += 1;
+        let mut stack = vec![*cell];
+        while let Some(at) = stack.pop() {
+            for (_, next) in maze.neighbors(&at) {
+                if seen.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+    }
+
+    let table = search::shortest_paths(maze, &start);
+    let path = table.path_to(&goal);
+    let decision_points = path.as_ref().map_or(0, |path| {
+        Some(start)
+            .into_iter()
+            .chain(path.iter().map(|&(_, pos)| pos))
+            .filter(|&pos| pos != goal)
+            .filter(|pos| {
+                // one of the neighbours is the way we came in
+                let ways_on = if *pos == start { degree(pos) } else { degree(pos) - 1 };
+                ways_on > 1
+            })
+            .count()
+    });
+
+    Stats {
+        cells: open.len(),
+        solution_length: path.as_ref().map(|p| p.len()),
+        solution_cost: table.cost(&goal),
+        dead_ends: dead_ends,
+        branching: branching,
+        corridors: corridors,
+        decision_points: decision_points,
+        loops: (edges / 2 + components).saturating_sub(open.len()),
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Open cells: {}", self.cells));
+        match self.solution_length {
+            Some(length) => {
+                try!(writeln!(f,
+                              "Solution: {} steps, cost {}",
+                              length,
+                              self.solution_cost.unwrap()))
+            }
+            None => try!(writeln!(f, "Solution: none")),
+        }
+        try!(writeln!(f, "Decision points on solution: {}", self.decision_points));
+        try!(writeln!(f, "Dead ends: {}", self.dead_ends));
+        try!(writeln!(f, "Loops: {}", self.loops));
+        try!(writeln!(f, "Branching:"));
+        for (d, &count) in self.branching.iter().enumerate().filter(|&(_, &c)| c > 0) {
+            try!(writeln!(f, "  {} ways: {}", d, count));
+        }
+        let longest = self.corridors.iter().max().cloned().unwrap_or(0);
+        let total: usize = self.corridors.iter().sum();
+        let mean = if self.corridors.is_empty() {
+            0.0
+        } else {
+            total as f64 / self.corridors.len() as f64
+        };
+        writeln!(f,
+                 "Corridors: {}, mean length {:.1}, longest {}",
+                 self.corridors.len(),
+                 mean,
+                 longest)
+    }
+}
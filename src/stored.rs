@@ -1,5 +1,85 @@
-use std::io::BufRead;
+use std::fmt;
+use std::io::{self, BufRead, Write};
 
-pub trait Stored {
-    fn load<R: BufRead>(reader: &mut R) -> Self;
+/// Why a puzzle couldn't be loaded. Lines and columns count from 1.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    UnknownChar { ch: char, line: usize, col: usize },
+    MissingStart,
+    MissingExit,
+    /// The same snake segment drawn twice.
+    DuplicateSegment { segment: usize, line: usize, col: usize },
+    /// A snake segment drawn nowhere, though a later one is.
+    MissingSegment(usize),
+    TooManyFruits { max: usize, line: usize, col: usize },
+    /// Anything else the format doesn't allow.
+    Invalid(String),
+}
+
+impl LoadError {
+    /// The same error for a file that had `lines` more lines before the part
+    /// that was read.
+    pub fn shifted(self, lines: usize) -> LoadError {
+        match self {
+            LoadError::UnknownChar { ch, line, col } => {
+                LoadError::UnknownChar {
+                    ch: ch,
+                    line: line + lines,
+                    col: col,
+                }
+            }
+            LoadError::DuplicateSegment { segment, line, col } => {
+                LoadError::DuplicateSegment {
+                    segment: segment,
+                    line: line + lines,
+                    col: col,
+                }
+            }
+            LoadError::TooManyFruits { max, line, col } => {
+                LoadError::TooManyFruits {
+                    max: max,
+                    line: line + lines,
+                    col: col,
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => write!(f, "{}", err),
+            LoadError::UnknownChar { ch, line, col } => {
+                write!(f, "{}:{}: unexpected character '{}'", line, col, ch)
+            }
+            LoadError::MissingStart => write!(f, "no start"),
+            LoadError::MissingExit => write!(f, "no exit"),
+            LoadError::DuplicateSegment { segment, line, col } => {
+                write!(f, "{}:{}: snake segment {} appears twice", line, col, segment)
+            }
+            LoadError::MissingSegment(segment) => write!(f, "snake segment {} is missing", segment),
+            LoadError::TooManyFruits { max, line, col } => {
+                write!(f, "{}:{}: too many fruits, at most {} are allowed", line, col, max)
+            }
+            LoadError::Invalid(ref why) => write!(f, "{}", why),
+        }
+    }
+}
+
+pub trait Stored: Sized {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError>;
+
+    /// Writes the puzzle in the format `load` reads back.
+    fn save<W: Write>(&self, _writer: &mut W) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "this puzzle type can't be saved"))
+    }
 }
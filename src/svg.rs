@@ -0,0 +1,140 @@
+use maze::Maze;
+use search::Graph;
+use std::io::{self, Write};
+
+/// Writes `maze` as an SVG with each cell `scale` units square. `path` is
+/// drawn as a line through the cells in order, broken wherever it jumps
+/// through a portal or around a wrapped edge. `expanded`, if given, lists
+/// cells in the order a search expanded them and is drawn as a heatmap from
+/// yellow (early) to red (late).
+pub fn render<W: Write>(maze: &Maze,
+                        path: &[(usize, usize)],
+                        expanded: Option<&[(usize, usize)]>,
+                        scale: usize,
+                        writer: &mut W)
+                        -> io::Result<()> {
+    let (width, height) = (maze.width() * scale, maze.height() * scale);
+    try!(header(writer, width, height));
+    try!(body(maze, path, expanded, scale, writer));
+    writeln!(writer, "</svg>")
+}
+
+/// Renders two solutions of the same maze next to each other, each under a
+/// caption, to compare which cells their searches expanded.
+pub fn render_pair<W: Write>(maze: &Maze,
+                             left: (&str, &[(usize, usize)], &[(usize, usize)]),
+                             right: (&str, &[(usize, usize)], &[(usize, usize)]),
+                             scale: usize,
+                             writer: &mut W)
+                             -> io::Result<()> {
+    let (width, height) = (maze.width() * scale, maze.height() * scale);
+    let (gap, caption) = (2 * scale, 2 * scale);
+    try!(header(writer, 2 * width + gap, height + caption));
+    for (i, &(title, path, expanded)) in [left, right].iter().enumerate() {
+        let x = i * (width + gap);
+        try!(writeln!(writer,
+                      "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\">{}\
+                       </text>",
+                      x,
+                      caption * 3 / 4,
+                      scale * 3 / 2,
+                      title));
+        try!(writeln!(writer, "<g transform=\"translate({},{})\">", x, caption));
+        try!(body(maze, path, Some(expanded), scale, writer));
+        try!(writeln!(writer, "</g>"));
+    }
+    writeln!(writer, "</svg>")
+}
+
+fn header<W: Write>(writer: &mut W, width: usize, height: usize) -> io::Result<()> {
+    try!(writeln!(writer,
+                  "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                   viewBox=\"0 0 {} {}\">",
+                  width,
+                  height,
+                  width,
+                  height));
+    writeln!(writer, "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", width, height)
+}
+
+fn body<W: Write>(maze: &Maze,
+                  path: &[(usize, usize)],
+                  expanded: Option<&[(usize, usize)]>,
+                  scale: usize,
+                  writer: &mut W)
+                  -> io::Result<()> {
+    if let Some(cells) = expanded {
+        try!(writeln!(writer, "<g fill-opacity=\"0.6\">"));
+        for (i, &(x, y)) in cells.iter().enumerate() {
+            let green = 255 - 255 * i / cells.len();
+            try!(cell(writer, x, y, scale, &format!("rgb(255,{},0)", green)));
+        }
+        try!(writeln!(writer, "</g>"));
+    }
+
+    // one rect per horizontal run of wall
+    try!(writeln!(writer, "<g fill=\"black\">"));
+    for y in 0..maze.height() {
+        let mut x = 0;
+        while x < maze.width() {
+            if maze.is_open(x, y) {
+                x += 1;
+                continue;
+            }
+            let run_start = x;
+            while x < maze.width() && !maze.is_open(x, y) {
+                x += 1;
+            }
+            try!(writeln!(writer,
+                          "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                          run_start * scale,
+                          y * scale,
+                          (x - run_start) * scale,
+                          scale));
+        }
+    }
+    try!(writeln!(writer, "</g>"));
+
+    let (sx, sy) = maze.start();
+    try!(cell(writer, sx, sy, scale, "rgb(40,180,40)"));
+    let (gx, gy) = maze.goal();
+    try!(cell(writer, gx, gy, scale, "rgb(40,80,220)"));
+
+    let mut segment: Vec<(usize, usize)> = vec![];
+    for &pos in path {
+        let joined = segment.last().map_or(true, |&(x, y)| {
+            let (dx, dy) = (x as isize - pos.0 as isize, y as isize - pos.1 as isize);
+            dx.abs() <= 1 && dy.abs() <= 1
+        });
+        if !joined {
+            try!(polyline(writer, &segment, scale));
+            segment.clear();
+        }
+        segment.push(pos);
+    }
+    polyline(writer, &segment, scale)
+}
+
+fn cell<W: Write>(writer: &mut W, x: usize, y: usize, scale: usize, fill: &str) -> io::Result<()> {
+    writeln!(writer,
+             "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+             x * scale,
+             y * scale,
+             scale,
+             scale,
+             fill)
+}
+
+fn polyline<W: Write>(writer: &mut W, cells: &[(usize, usize)], scale: usize) -> io::Result<()> {
+    if cells.len() < 2 {
+        return Ok(());
+    }
+    let points: Vec<String> = cells.iter()
+        .map(|&(x, y)| format!("{},{}", x * scale + scale / 2, y * scale + scale / 2))
+        .collect();
+    writeln!(writer,
+             "<polyline points=\"{}\" fill=\"none\" stroke=\"rgb(220,40,40)\" \
+              stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+             points.join(" "),
+             (scale + 2) / 3)
+}
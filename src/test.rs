@@ -1,14 +1,16 @@
 #![feature(test)]
 extern crate test;
+mod generator;
 mod maze;
 mod search;
+use generator::MazeGenerator;
 use rand::{thread_rng, Rng};
 use test::Bencher;
  
 fn test_data() -> Vec<maze::Maze> {
     let n = 1;
     let (w, h) = (400, 400);
-    (0..n).map(|n| maze::Maze::random(w, h)).collect::<Vec<maze::Maze>>()
+    (0..n).map(|n| generator::Backtracker.generate(w, h, false, &mut thread_rng())).collect::<Vec<maze::Maze>>()
 }
 
 #[bench]
@@ -0,0 +1,246 @@
+use maze::Maze;
+use search::Graph;
+use std::fmt;
+use std::io::BufRead;
+use stored::{LoadError, Stored};
+
+const NORTH: u8 = 1;
+const EAST: u8 = 2;
+const SOUTH: u8 = 4;
+const WEST: u8 = 8;
+
+/// A maze where walls sit on the edges between cells rather than filling
+/// cells of their own. Each cell keeps a bit for each side that is walled.
+///
+/// The text format is the common one drawn with `+` posts, `-` and `|` walls:
+///
+/// ```text
+/// +---+---+
+/// | @     |
+/// +---+   +
+/// | X     |
+/// +---+---+
+/// ```
+///
+/// Cells can be any width, taken from the gap between the first two posts.
+/// `@` and `X` inside a cell mark the start and goal, which otherwise default
+/// to the top left and bottom right cells.
+pub struct ThinMaze {
+    width: usize,
+    height: usize,
+    walls: Vec<u8>,
+    marked: Vec<bool>,
+    start: (usize, usize),
+    goal: (usize, usize),
+}
+
+impl Stored for ThinMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let text: Vec<String> = try!(reader.lines().collect());
+        // blank lines are skipped, but errors give lines as numbered in the file
+        let (numbers, lines): (Vec<usize>, Vec<Vec<char>>) = text.iter()
+            .zip(1..)
+            .filter(|&(l, _)| !l.is_empty())
+            .map(|(l, n)| (n, l.chars().collect()))
+            .unzip();
+        if lines.is_empty() {
+            return Err(LoadError::Invalid("empty map".to_string()));
+        }
+        let top = &lines[0];
+        let span = match top.iter().skip(1).position(|&ch| ch == '+') {
+            Some(gap) => gap + 1,
+            None => return Err(LoadError::Invalid("expected a row of '+' posts".to_string())),
+        };
+        let width = (top.len() - 1) / span;
+        let height = (lines.len() - 1) / 2;
+        if width == 0 || height == 0 {
+            return Err(LoadError::Invalid("expected at least one cell".to_string()));
+        }
+        let mut maze = ThinMaze::new(width, height);
+        maze.walls = vec![0; width * height];
+        let at = |y: usize, x: usize| lines[y].get(x).cloned().unwrap_or(' ');
+        for r in 0..height {
+            for c in 0..width {
+                let (x, y) = (c * span, r * 2);
+                let mut walls = 0;
+                if at(y, x + 1) == '-' {
+                    walls |= NORTH;
+                }
+                if at(y + 2, x + 1) == '-' {
+                    walls |= SOUTH;
+                }
+                if at(y + 1, x) == '|' {
+                    walls |= WEST;
+                }
+                if at(y + 1, x + span) == '|' {
+                    walls |= EAST;
+                }
+                maze.walls[r * width + c] = walls;
+                for col in x + 1..x + span {
+                    match at(y + 1, col) {
+                        '@' => maze.start = (c, r),
+                        'X' => maze.goal = (c, r),
+                        ' ' => {}
+                        ch => {
+                            return Err(LoadError::UnknownChar {
+                                ch: ch,
+                                line: numbers[y + 1],
+                                col: col + 1,
+                            })
+                        }
+                    }
+                }
+            }
+        }
+        Ok(maze)
+    }
+}
+
+impl ThinMaze {
+    /// A maze with every wall up.
+    pub fn new(width: usize, height: usize) -> ThinMaze {
+        ThinMaze {
+            width: width,
+            height: height,
+            walls: vec![NORTH | EAST | SOUTH | WEST; width * height],
+            marked: vec![false; width * height],
+            start: (0, 0),
+            goal: (width - 1, height - 1),
+        }
+    }
+
+    /// Reads the walls off a block maze, whose cells sit at odd coordinates.
+    pub fn from_block(block: &Maze) -> ThinMaze {
+        assert!(block.width() % 2 == 1 && block.height() % 2 == 1,
+                format!("mis-sized maze {}x{}", block.width(), block.height()));
+        let mut maze = ThinMaze::new(block.width() / 2, block.height() / 2);
+        for r in 0..maze.height {
+            for c in 0..maze.width {
+                let (x, y) = (2 * c + 1, 2 * r + 1);
+                let mut walls = 0;
+                for &(bit, wx, wy) in &[(NORTH, x, y - 1), (EAST, x + 1, y), (SOUTH, x, y + 1),
+                                        (WEST, x - 1, y)] {
+                    if !block.is_open(wx, wy) {
+                        walls |= bit;
+                    }
+                }
+                maze.walls[r * maze.width + c] = walls;
+            }
+        }
+        // openings in the outer wall count as the cell next to them
+        let cell = |(x, y): (usize, usize)| {
+            (if x > 0 { (x - 1) / 2 } else { 0 }, if y > 0 { (y - 1) / 2 } else { 0 })
+        };
+        maze.start = cell(block.start());
+        maze.goal = cell(block.goal());
+        maze
+    }
+
+    /// The same maze with walls as cells of their own.
+    pub fn to_block(&self) -> Maze {
+        let mut block = Maze::new(2 * self.width + 1, 2 * self.height + 1);
+        for r in 0..self.height {
+            for c in 0..self.width {
+                let (x, y) = (2 * c + 1, 2 * r + 1);
+                let walls = self.walls[r * self.width + c];
+                block.set_open(x, y);
+                for &(bit, wx, wy) in &[(NORTH, x, y - 1), (EAST, x + 1, y), (SOUTH, x, y + 1),
+                                        (WEST, x - 1, y)] {
+                    if walls & bit == 0 {
+                        block.set_open(wx, wy);
+                    }
+                }
+            }
+        }
+        block.set_start(2 * self.start.0 + 1, 2 * self.start.1 + 1);
+        block.set_goal(2 * self.goal.0 + 1, 2 * self.goal.1 + 1);
+        block
+    }
+
+    pub fn mark(&mut self, x: usize, y: usize) {
+        self.marked[y * self.width + x] = true;
+    }
+
+    fn wall(&self, x: usize, y: usize, side: u8) -> bool {
+        self.walls[y * self.width + x] & side != 0
+    }
+}
+
+impl Graph for ThinMaze {
+    type Node = (usize, usize);
+    type Edge = char;
+
+    fn null_edge() -> char {
+        ' '
+    }
+
+    fn start(&self) -> (usize, usize) {
+        self.start
+    }
+
+    fn goal(&self) -> (usize, usize) {
+        self.goal
+    }
+
+    fn neighbors(&self, &(x, y): &(usize, usize)) -> Vec<(char, (usize, usize))> {
+        let mut list = Vec::with_capacity(4);
+        if x > 0 && !self.wall(x, y, WEST) {
+            list.push(('<', (x - 1, y)));
+        }
+        if y > 0 && !self.wall(x, y, NORTH) {
+            list.push(('^', (x, y - 1)));
+        }
+        if x + 1 < self.width && !self.wall(x, y, EAST) {
+            list.push(('>', (x + 1, y)));
+        }
+        if y + 1 < self.height && !self.wall(x, y, SOUTH) {
+            list.push(('v', (x, y + 1)));
+        }
+        list
+    }
+
+    fn distance(&self, &(x1, y1): &(usize, usize), &(x2, y2): &(usize, usize)) -> usize {
+        let dx = if x1 < x2 { x2 - x1 } else { x1 - x2 };
+        let dy = if y1 < y2 { y2 - y1 } else { y1 - y2 };
+        (dx + dy) * 1000
+    }
+}
+
+impl fmt::Display for ThinMaze {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.height + 1 {
+            // the posts and walls along the top of row y
+            let mut line = String::from("+");
+            for x in 0..self.width {
+                let walled = if y < self.height {
+                    self.wall(x, y, NORTH)
+                } else {
+                    self.wall(x, y - 1, SOUTH)
+                };
+                line.push_str(if walled { "---+" } else { "   +" });
+            }
+            try!(writeln!(f, "{}", line));
+            if y == self.height {
+                break;
+            }
+            let mut line = String::from(if self.wall(0, y, WEST) { "|" } else { " " });
+            for x in 0..self.width {
+                let ch = if (x, y) == self.start {
+                    '@'
+                } else if (x, y) == self.goal {
+                    'X'
+                } else if self.marked[y * self.width + x] {
+                    '.'
+                } else {
+                    ' '
+                };
+                line.push(' ');
+                line.push(ch);
+                line.push(' ');
+                line.push(if self.wall(x, y, EAST) { '|' } else { ' ' });
+            }
+            try!(writeln!(f, "{}", line));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use maze::Maze;
+use search::Graph;
+use std::collections::HashMap;
+use std::io::{BufRead, Cursor};
+use stored::{LoadError, Stored};
+
+/// A maze patrolled by obstacles on fixed, repeating schedules.
+///
+/// The map is the plain maze format with lowercase letters marking where
+/// each obstacle starts. After a line starting with `-`, each obstacle gets a
+/// line like `a: >>..<<` giving one action per tick: `<`, `^`, `>` or `v` to
+/// move, `.` to stay put and `_` to stay put but stop blocking (an open gate)
+/// until the next action. A schedule must end where it started. Obstacles
+/// without a schedule never move.
+pub struct TimedMaze {
+    maze: Maze,
+    labels: Vec<char>,
+    // where each obstacle blocks at each tick of the period, if anywhere
+    schedule: Vec<Vec<Option<(usize, usize)>>>,
+    period: usize,
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl Stored for TimedMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let lines: Vec<String> = try!(reader.lines().collect());
+        let split = lines.iter().position(|l| l.starts_with('-')).unwrap_or(lines.len());
+        let mut starts = HashMap::new();
+        let maze = try!(Maze::load_with(&mut Cursor::new(lines[..split].join("\n")), |ch, x, y| {
+            match ch {
+                'a'...'z' => starts.insert(ch, (x, y)),
+                _ => return None,
+            };
+            Some(true)
+        }));
+        let mut scripts = HashMap::new();
+        let script_lines = lines.iter().zip(1..).skip(split + 1);
+        for (line, n) in script_lines.filter(|&(l, _)| !l.trim().is_empty()) {
+            let mut parts = line.splitn(2, ':');
+            let label = parts.next().unwrap().trim();
+            let script = match parts.next() {
+                Some(script) => script.trim(),
+                None => return Err(LoadError::Invalid(format!("{}: expected 'label: actions'", n))),
+            };
+            let label = label.chars().next().unwrap_or(' ');
+            if !starts.contains_key(&label) {
+                return Err(LoadError::Invalid(format!("{}: no obstacle '{}' in the map", n, label)));
+            }
+            if script.is_empty() {
+                return Err(LoadError::Invalid(format!("{}: obstacle '{}' has no moves", n, label)));
+            }
+            scripts.insert(label, script.chars().collect::<Vec<char>>());
+        }
+        let mut labels: Vec<char> = starts.keys().cloned().collect();
+        labels.sort();
+        let period = labels.iter()
+            .map(|l| scripts.get(l).map_or(1, |s| s.len()))
+            .fold(1, |p, n| p / gcd(p, n) * n);
+        let mut schedule = vec![];
+        for label in &labels {
+            let start = starts[label];
+            let script = scripts.get(label).cloned().unwrap_or(vec!['.']);
+            let mut ticks = vec![Some(start)];
+            let (mut x, mut y) = (start.0 as isize, start.1 as isize);
+            for t in 1..period + 1 {
+                let action = script[(t - 1) % script.len()];
+                match action {
+                    '<' => x -= 1,
+                    '>' => x += 1,
+                    '^' => y -= 1,
+                    'v' => y += 1,
+                    '.' | '_' => {}
+                    _ => {
+                        return Err(LoadError::Invalid(format!("unexpected action '{}' for '{}'",
+                                                              action,
+                                                              label)))
+                    }
+                }
+                if x < 0 || y < 0 || x as usize >= maze.width() || y as usize >= maze.height() {
+                    return Err(LoadError::Invalid(format!("obstacle '{}' leaves the map", label)));
+                }
+                let pos = (x as usize, y as usize);
+                if t == period {
+                    if pos != start {
+                        return Err(LoadError::Invalid(format!("schedule for '{}' doesn't \
+                                                               return to its start",
+                                                              label)));
+                    }
+                } else {
+                    ticks.push(if action == '_' { None } else { Some(pos) });
+                }
+            }
+            schedule.push(ticks);
+        }
+        Ok(TimedMaze {
+            maze: maze,
+            labels: labels,
+            schedule: schedule,
+            period: period,
+        })
+    }
+}
+
+impl TimedMaze {
+    fn blocked(&self, pos: (usize, usize), t: usize) -> bool {
+        self.schedule.iter().any(|ticks| ticks[t % self.period] == Some(pos))
+    }
+
+    /// Whether an obstacle moves from `to` into `from` while we move from
+    /// `from` into `to`, passing straight through us.
+    fn swaps(&self, from: (usize, usize), to: (usize, usize), t: usize) -> bool {
+        self.schedule.iter().any(|ticks| {
+            ticks[t % self.period] == Some(to) && ticks[(t + 1) % self.period] == Some(from)
+        })
+    }
+
+    /// Prints the maze at `tick`, with us at `pos`.
+    pub fn print(&self, pos: (usize, usize), tick: usize) {
+        let rows = self.maze.render_with(|x, y, ch| {
+            if (x, y) == pos {
+                'o'
+            } else if let Some(i) = self.schedule
+                .iter()
+                .position(|ticks| ticks[tick % self.period] == Some((x, y))) {
+                self.labels[i]
+            } else {
+                ch
+            }
+        });
+        for row in rows {
+            println!("{}", row);
+        }
+        println!("");
+    }
+}
+
+impl Graph for TimedMaze {
+    // position and time into the schedule
+    type Node = ((usize, usize), usize);
+    type Edge = char;
+
+    fn null_edge() -> char {
+        ' '
+    }
+
+    fn start(&self) -> ((usize, usize), usize) {
+        (self.maze.start(), 0)
+    }
+
+    fn goal(&self) -> ((usize, usize), usize) {
+        // once out it doesn't matter when, like snake's empty exit state
+        (self.maze.goal(), 0)
+    }
+
+    fn neighbors(&self, &(pos, t): &((usize, usize), usize)) -> Vec<(char, ((usize, usize), usize))> {
+        let goal = self.maze.goal();
+        let next_t = (t + 1) % self.period;
+        Some(('.', pos))
+            .into_iter()
+            .chain(self.maze.neighbors(&pos))
+            .filter(|&(_, next)| !self.blocked(next, next_t) && !self.swaps(pos, next, t))
+            .map(|(dir, next)| (dir, (next, if next == goal { 0 } else { next_t })))
+            .collect()
+    }
+
+    fn distance(&self,
+                &(a, _): &((usize, usize), usize),
+                &(b, _): &((usize, usize), usize))
+                -> usize {
+        // waiting in place still takes a tick
+        if a == b { 1000 } else { self.maze.distance(&a, &b) }
+    }
+}
@@ -0,0 +1,189 @@
+use maze::Maze;
+use search::{self, Graph};
+use std::fmt;
+use std::io::BufRead;
+use stored::{LoadError, Stored};
+
+/// Largest number of waypoints ordered exactly with Held-Karp. Beyond this
+/// the 2^n table gets too big and we fall back to 2-opt.
+const HELD_KARP_LIMIT: usize = 12;
+
+/// A maze with `+` waypoints that must all be visited between `@` and `X`.
+pub struct TourMaze {
+    maze: Maze,
+    waypoints: Vec<(usize, usize)>,
+}
+
+pub struct Tour {
+    /// Waypoint indices in visiting order.
+    pub order: Vec<usize>,
+    pub cost: usize,
+    pub route: Vec<(char, (usize, usize))>,
+}
+
+impl Stored for TourMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let mut waypoints = vec![];
+        let maze = try!(Maze::load_with(reader, |ch, x, y| {
+            match ch {
+                '+' => waypoints.push((x, y)),
+                _ => return None,
+            }
+            Some(true)
+        }));
+        Ok(TourMaze {
+            maze: maze,
+            waypoints: waypoints,
+        })
+    }
+}
+
+/// Cost of visiting waypoints in `order`, where `dist[i][j]` is the cost
+/// between stops and stop 0 is the start, 1..n the waypoints and n + 1 the goal.
+fn tour_cost(dist: &Vec<Vec<usize>>, order: &[usize]) -> usize {
+    let goal = dist.len() - 1;
+    let mut cost = 0;
+    let mut at = 0;
+    for &w in order {
+        cost += dist[at][w + 1];
+        at = w + 1;
+    }
+    cost + dist[at][goal]
+}
+
+fn held_karp(dist: &Vec<Vec<usize>>) -> Vec<usize> {
+    let n = dist.len() - 2;
+    let full = (1 << n) - 1;
+    // best[mask][i]: cheapest way from the start through `mask`, ending at i
+    let mut best = vec![vec![None; n]; 1 << n];
+    for i in 0..n {
+        best[1 << i][i] = Some((dist[0][i + 1], None));
+    }
+    for mask in 1..full + 1 {
+        for last in 0..n {
+            let cost = match best[mask][last] {
+                Some((cost, _)) => cost,
+                None => continue,
+            };
+            for next in 0..n {
+                if mask & (1 << next) != 0 {
+                    continue;
+                }
+                let candidate = cost + dist[last + 1][next + 1];
+                let slot = &mut best[mask | (1 << next)][next];
+                if slot.map_or(true, |(old, _)| candidate < old) {
+                    *slot = Some((candidate, Some(last)));
+                }
+            }
+        }
+    }
+    let mut last = (0..n)
+        .min_by_key(|&i| best[full][i].unwrap().0 + dist[i + 1][n + 1])
+        .unwrap();
+    let mut mask = full;
+    let mut order = vec![];
+    loop {
+        order.push(last);
+        let prior = best[mask][last].unwrap().1;
+        mask &= !(1 << last);
+        match prior {
+            Some(p) => last = p,
+            None => break,
+        }
+    }
+    order.reverse();
+    order
+}
+
+fn two_opt(dist: &Vec<Vec<usize>>) -> Vec<usize> {
+    let n = dist.len() - 2;
+    // greedy nearest neighbour tour to start from
+    let mut order = vec![];
+    let mut at = 0;
+    let mut left: Vec<usize> = (0..n).collect();
+    while !left.is_empty() {
+        let (k, _) = left.iter()
+            .enumerate()
+            .min_by_key(|&(_, &w)| dist[at][w + 1])
+            .unwrap();
+        at = left.remove(k) + 1;
+        order.push(at - 1);
+    }
+    let mut cost = tour_cost(dist, &order);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..n {
+            for j in i + 1..n {
+                order[i..j + 1].reverse();
+                let candidate = tour_cost(dist, &order);
+                if candidate < cost {
+                    cost = candidate;
+                    improved = true;
+                } else {
+                    order[i..j + 1].reverse();
+                }
+            }
+        }
+    }
+    order
+}
+
+impl TourMaze {
+    /// Finds the cheapest route from the start through every waypoint to the
+    /// goal, or `None` if some stop can't be reached.
+    pub fn solve(&self) -> Option<Tour> {
+        let stops: Vec<(usize, usize)> = Some(self.maze.start())
+            .into_iter()
+            .chain(self.waypoints.iter().cloned())
+            .chain(Some(self.maze.goal()))
+            .collect();
+        let tables: Vec<_> = stops[..stops.len() - 1]
+            .iter()
+            .map(|stop| search::shortest_paths(&self.maze, stop))
+            .collect();
+        let mut dist = vec![vec![0; stops.len()]; stops.len()];
+        for (i, table) in tables.iter().enumerate() {
+            for (j, stop) in stops.iter().enumerate() {
+                dist[i][j] = match table.cost(stop) {
+                    Some(cost) => cost,
+                    None => return None,
+                };
+            }
+        }
+        let order = if self.waypoints.is_empty() {
+            vec![]
+        } else if self.waypoints.len() <= HELD_KARP_LIMIT {
+            held_karp(&dist)
+        } else {
+            two_opt(&dist)
+        };
+        let mut route = vec![];
+        let mut at = 0;
+        for stop in order.iter().map(|&w| w + 1).chain(Some(stops.len() - 1)) {
+            route.extend(tables[at].path_to(&stops[stop]).unwrap());
+            at = stop;
+        }
+        Some(Tour {
+            cost: tour_cost(&dist, &order),
+            order: order,
+            route: route,
+        })
+    }
+
+    pub fn mark(&mut self, x: usize, y: usize) {
+        self.maze.mark(x, y);
+    }
+}
+
+impl fmt::Display for TourMaze {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self.maze.render_with(|x, y, ch| {
+            if self.waypoints.contains(&(x, y)) { '+' } else { ch }
+        });
+        for row in rows {
+            try!(writeln!(f, "{}", row));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use passages::{self, Passages};
+use rand::Rng;
+use search::Graph;
+use std::fmt;
+use std::io::BufRead;
+use stored::{LoadError, Stored};
+
+// Every triangle has a west and east neighbour and one across its flat side:
+// below for upward-pointing triangles, above for downward-pointing ones.
+const WEST: usize = 0;
+const EAST: usize = 1;
+const ACROSS: usize = 2;
+const OPPOSITE: [usize; 3] = [EAST, WEST, ACROSS];
+
+/// A maze of alternating triangles. Cell `(c, r)` points up when `c + r` is
+/// even and down otherwise.
+///
+/// The text format puts cell `(c, r)` at column `2c` of line `2r`, written
+/// `o` (or `@`/`X` for start and goal). A `-` after a cell opens its east
+/// side, and a `|` below an upward triangle opens its base.
+///
+/// ```text
+/// @-o o-o
+/// |   |
+/// o-o-o-X
+/// ```
+pub struct TriMaze {
+    width: usize,
+    height: usize,
+    // bit d set when side d is open
+    passages: Vec<u8>,
+    marked: Vec<bool>,
+    start: (usize, usize),
+    goal: (usize, usize),
+}
+
+impl TriMaze {
+    pub fn new(width: usize, height: usize) -> TriMaze {
+        assert!(width > 0 && height > 0,
+                format!("mis-sized maze {}x{}", width, height));
+        TriMaze {
+            width: width,
+            height: height,
+            passages: vec![0; width * height],
+            marked: vec![false; width * height],
+            start: (0, 0),
+            goal: (width - 1, height - 1),
+        }
+    }
+
+    fn points_up(c: usize, r: usize) -> bool {
+        (c + r) % 2 == 0
+    }
+
+    fn is_passage(&self, c: usize, r: usize, d: usize) -> bool {
+        self.passages[r * self.width + c] & (1 << d) != 0
+    }
+
+    pub fn random<R: Rng>(width: usize, height: usize, rng: &mut R) -> TriMaze {
+        let mut maze = Self::new(width, height);
+        passages::backtrack(&mut maze, rng);
+        maze
+    }
+
+    pub fn mark(&mut self, c: usize, r: usize) {
+        self.marked[r * self.width + c] = true;
+    }
+
+    pub fn is_marked(&self, c: usize, r: usize) -> bool {
+        self.marked[r * self.width + c]
+    }
+}
+
+impl Passages for TriMaze {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn adjacents(&self, c: usize, r: usize) -> Vec<(usize, (usize, usize))> {
+        let mut list = Vec::with_capacity(3);
+        if c > 0 {
+            list.push((WEST, (c - 1, r)));
+        }
+        if c < self.width - 1 {
+            list.push((EAST, (c + 1, r)));
+        }
+        if Self::points_up(c, r) {
+            if r < self.height - 1 {
+                list.push((ACROSS, (c, r + 1)));
+            }
+        } else if r > 0 {
+            list.push((ACROSS, (c, r - 1)));
+        }
+        list
+    }
+
+    fn join(&mut self, c: usize, r: usize, d: usize, nc: usize, nr: usize) {
+        self.passages[r * self.width + c] |= 1 << d;
+        self.passages[nr * self.width + nc] |= 1 << OPPOSITE[d];
+    }
+}
+
+impl Stored for TriMaze {
+    fn load<R: BufRead>(reader: &mut R) -> Result<Self, LoadError> {
+        let text: Vec<String> = try!(reader.lines().collect());
+        let lines: Vec<Vec<char>> = text.iter().map(|l| l.chars().collect()).collect();
+        let h = (lines.len() + 1) / 2;
+        let w = lines.iter().map(|l| (l.len() + 1) / 2).max().unwrap_or(0);
+        if w == 0 {
+            return Err(LoadError::Invalid("empty map".to_string()));
+        }
+        let mut maze = TriMaze::new(w, h);
+        let at = |x: usize, y: usize| lines.get(y).and_then(|l| l.get(x)).cloned().unwrap_or(' ');
+        for r in 0..h {
+            for c in 0..w {
+                let (x, y) = (2 * c, 2 * r);
+                match at(x, y) {
+                    ' ' | 'o' => {}
+                    '@' => maze.start = (c, r),
+                    'X' => maze.goal = (c, r),
+                    ch => {
+                        return Err(LoadError::UnknownChar {
+                            ch: ch,
+                            line: y + 1,
+                            col: x + 1,
+                        })
+                    }
+                }
+                if at(x + 1, y) == '-' {
+                    try!(maze.load_passage(c, r, EAST, x + 1, y));
+                }
+                if at(x, y + 1) == '|' {
+                    // a downward triangle's base is above it, not below
+                    if !Self::points_up(c, r) {
+                        return Err(LoadError::Invalid(format!("{}:{}: '|' under a downward \
+                                                               triangle",
+                                                              y + 2,
+                                                              x + 1)));
+                    }
+                    try!(maze.load_passage(c, r, ACROSS, x, y + 1));
+                }
+            }
+        }
+        Ok(maze)
+    }
+}
+
+impl Graph for TriMaze {
+    type Node = (usize, usize);
+    type Edge = char;
+
+    fn null_edge() -> char {
+        ' '
+    }
+
+    fn start(&self) -> (usize, usize) {
+        self.start
+    }
+
+    fn goal(&self) -> (usize, usize) {
+        self.goal
+    }
+
+    fn neighbors(&self, &(c, r): &(usize, usize)) -> Vec<(char, (usize, usize))> {
+        self.adjacents(c, r)
+            .into_iter()
+            .filter(|&(d, _)| self.is_passage(c, r, d))
+            .map(|(d, (nc, nr))| {
+                let dir = match d {
+                    WEST => '<',
+                    EAST => '>',
+                    _ => if nr > r { 'v' } else { '^' },
+                };
+                (dir, (nc, nr))
+            })
+            .collect()
+    }
+
+    fn distance(&self, &(c1, r1): &(usize, usize), &(c2, r2): &(usize, usize)) -> usize {
+        // each step changes exactly one coordinate, so manhattan never overestimates
+        fn dist(a: usize, b: usize) -> usize {
+            if a < b { b - a } else { a - b }
+        }
+        let d = dist(c1, c2) + dist(r1, r2);
+        if d < 2 { d * 1000 } else { d * 1001 }
+    }
+}
+
+impl fmt::Display for TriMaze {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut canvas = vec![vec![' '; 2 * self.width - 1]; 2 * self.height - 1];
+        for r in 0..self.height {
+            for c in 0..self.width {
+                let (x, y) = (2 * c, 2 * r);
+                let up = Self::points_up(c, r);
+                canvas[y][x] = if (c, r) == self.start {
+                    '@'
+                } else if (c, r) == self.goal {
+                    'X'
+                } else if self.is_marked(c, r) {
+                    if up { '▲' } else { '▼' }
+                } else {
+                    if up { '△' } else { '▽' }
+                };
+                if self.is_passage(c, r, EAST) {
+                    canvas[y][x + 1] = '-';
+                }
+                if up && self.is_passage(c, r, ACROSS) {
+                    canvas[y + 1][x] = '|';
+                }
+            }
+        }
+        for line in canvas {
+            let line: String = line.into_iter().collect();
+            try!(writeln!(f, "{}", line.trim_right()));
+        }
+        try!(f.write_str("\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use bits::BitSet;
+use maze::Maze;
+use search::Graph;
+use std::collections::{HashMap, VecDeque};
+
+/// Moves clockwise from north, so turning right adds one.
+const DIRS: [char; 4] = ['^', '>', 'v', '<'];
+
+/// Classic ways to solve a maze from inside it, using only what someone
+/// walking it could see and chalk on the floor. They move in four directions
+/// and ignore portals.
+#[derive(Clone, Copy)]
+pub enum Walker {
+    /// Keep your left hand on the wall.
+    LeftHand,
+    /// Keep your right hand on the wall.
+    RightHand,
+    /// Head towards the goal, following the wall with the left hand around
+    /// any obstacle until the turns taken add back up to zero.
+    Pledge,
+    /// Chalk each passage as you walk it and never walk one a third time.
+    Tremaux,
+    /// Wall up dead ends until only the way through is left. This one needs
+    /// the whole map, and its steps are the cells it fills.
+    DeadEndFilling,
+}
+
+/// How a walker got on.
+pub struct Walk {
+    pub solved: bool,
+    pub steps: usize,
+    /// Every cell walked, in order from the start, or the path left after
+    /// filling.
+    pub route: Vec<(usize, usize)>,
+}
+
+/// The open cells a step away from `pos`, with the index of the direction
+/// to each.
+fn exits(maze: &Maze, pos: (usize, usize)) -> Vec<(usize, (usize, usize))> {
+    maze.neighbors(&pos)
+        .into_iter()
+        .filter_map(|(edge, next)| DIRS.iter().position(|&d| d == edge).map(|dir| (dir, next)))
+        .collect()
+}
+
+fn ahead(maze: &Maze, pos: (usize, usize), dir: usize) -> Option<(usize, usize)> {
+    exits(maze, pos).into_iter().find(|&(d, _)| d == dir).map(|(_, next)| next)
+}
+
+pub fn walk(maze: &Maze, walker: Walker) -> Walk {
+    match walker {
+        // turns to try in order, relative to the heading
+        Walker::LeftHand => follow_wall(maze, &[3, 0, 1, 2]),
+        Walker::RightHand => follow_wall(maze, &[1, 0, 3, 2]),
+        Walker::Pledge => pledge(maze),
+        Walker::Tremaux => tremaux(maze),
+        Walker::DeadEndFilling => fill_dead_ends(maze),
+    }
+}
+
+/// Steps a wall-following walk takes before giving up. A plain wall follower
+/// that takes more is going round in circles, since it can only face four
+/// ways in each cell.
+fn limit(maze: &Maze) -> usize {
+    4 * maze.open_count()
+}
+
+fn follow_wall(maze: &Maze, turns: &[usize]) -> Walk {
+    let (mut pos, goal) = (maze.start(), maze.goal());
+    let mut route = vec![pos];
+    let mut dir = 0;
+    let limit = limit(maze);
+    while pos != goal && route.len() <= limit {
+        let step = turns.iter()
+            .map(|&turn| (dir + turn) % 4)
+            .filter_map(|d| ahead(maze, pos, d).map(|next| (d, next)))
+            .next();
+        match step {
+            Some((d, next)) => {
+                dir = d;
+                pos = next;
+                route.push(pos);
+            }
+            None => break,
+        }
+    }
+    Walk {
+        solved: pos == goal,
+        steps: route.len() - 1,
+        route: route,
+    }
+}
+
+fn pledge(maze: &Maze) -> Walk {
+    let (mut pos, goal) = (maze.start(), maze.goal());
+    let (dx, dy) = (goal.0 as isize - pos.0 as isize, goal.1 as isize - pos.1 as isize);
+    let main = if dx.abs() > dy.abs() {
+        if dx > 0 { 1 } else { 3 }
+    } else if dy > 0 {
+        2
+    } else {
+        0
+    };
+    let mut route = vec![pos];
+    let mut dir = main;
+    // quarter turns taken clockwise since leaving the main heading
+    let mut turned: isize = 0;
+    // with the wall on the left, or heading straight on when free of it
+    let following = [(3, -1), (0, 0), (1, 1), (2, 2)];
+    let free = [(0, 0), (1, 1), (2, 2), (3, -1)];
+    let limit = limit(maze);
+    while pos != goal && route.len() <= limit {
+        let turns = if turned == 0 { &free } else { &following };
+        let step = turns.iter()
+            .filter_map(|&(turn, count)| {
+                let d = (dir + turn) % 4;
+                ahead(maze, pos, d).map(|next| (d, count, next))
+            })
+            .next();
+        match step {
+            Some((d, count, next)) => {
+                dir = d;
+                turned += count;
+                pos = next;
+                route.push(pos);
+            }
+            None => break,
+        }
+    }
+    Walk {
+        solved: pos == goal,
+        steps: route.len() - 1,
+        route: route,
+    }
+}
+
+fn tremaux(maze: &Maze) -> Walk {
+    let (mut pos, goal) = (maze.start(), maze.goal());
+    let mut route = vec![pos];
+    let mut marks: HashMap<((usize, usize), (usize, usize)), u8> = HashMap::new();
+    let passage = |a: (usize, usize), b: (usize, usize)| if a < b { (a, b) } else { (b, a) };
+    let count = |marks: &HashMap<_, u8>, a, b| *marks.get(&passage(a, b)).unwrap_or(&0);
+    let mut came = None;
+    while pos != goal {
+        let ways: Vec<(usize, usize)> = exits(maze, pos).into_iter().map(|(_, n)| n).collect();
+        let others: Vec<(usize, usize)> =
+            ways.iter().cloned().filter(|&n| Some(n) != came).collect();
+        let seen = others.iter().any(|&n| count(&marks, pos, n) > 0);
+        let fresh = others.iter().cloned().find(|&n| count(&marks, pos, n) == 0);
+        let next = match (seen, fresh, came) {
+            (false, Some(n), _) => Some(n),
+            // back the way we came rather than close a loop
+            (true, _, Some(back)) if count(&marks, pos, back) == 1 => Some(back),
+            _ => {
+                ways.iter()
+                    .cloned()
+                    .filter(|&n| count(&marks, pos, n) < 2)
+                    .min_by_key(|&n| count(&marks, pos, n))
+            }
+        };
+        match next {
+            Some(n) => {
+                *marks.entry(passage(pos, n)).or_insert(0) += 1;
+                came = Some(pos);
+                pos = n;
+                route.push(pos);
+            }
+            None => break,
+        }
+    }
+    Walk {
+        solved: pos == goal,
+        steps: route.len() - 1,
+        route: route,
+    }
+}
+
+fn fill_dead_ends(maze: &Maze) -> Walk {
+    let (start, goal) = (maze.start(), maze.goal());
+    let index = |(x, y): (usize, usize)| y * maze.width() + x;
+    let mut filled = BitSet::new(maze.width() * maze.height());
+    let is_dead_end = |filled: &BitSet, pos: (usize, usize)| {
+        pos != start && pos != goal &&
+        exits(maze, pos).iter().filter(|&&(_, n)| !filled.contains(index(n))).count() <= 1
+    };
+    let mut dead_ends = vec![];
+    for y in 0..maze.height() {
+        for x in 0..maze.width() {
+            if maze.is_open(x, y) && is_dead_end(&filled, (x, y)) {
+                dead_ends.push((x, y));
+            }
+        }
+    }
+    let mut steps = 0;
+    while let Some(pos) = dead_ends.pop() {
+        if !filled.insert(index(pos)) {
+            continue;
+        }
+        steps += 1;
+        for (_, n) in exits(maze, pos) {
+            if !filled.contains(index(n)) && is_dead_end(&filled, n) {
+                dead_ends.push(n);
+            }
+        }
+    }
+
+    // what's left is the way through, plus any loops; filled cells double
+    // as the visited set while finding it
+    let mut prior = HashMap::new();
+    let mut queue = VecDeque::new();
+    filled.insert(index(start));
+    queue.push_back(start);
+    while let Some(pos) = queue.pop_front() {
+        if pos == goal {
+            break;
+        }
+        for (_, n) in exits(maze, pos) {
+            if filled.insert(index(n)) {
+                prior.insert(n, pos);
+                queue.push_back(n);
+            }
+        }
+    }
+    let mut route = vec![goal];
+    while let Some(&before) = prior.get(route.last().unwrap()) {
+        route.push(before);
+    }
+    route.reverse();
+    let solved = route[0] == start;
+    Walk {
+        solved: solved,
+        steps: steps,
+        route: if solved { route } else { vec![] },
+    }
+}